@@ -0,0 +1,167 @@
+const BOOT_REGION_SECTORS: u64 = 12;
+const CHECKSUM_SECTOR: u64 = 11;
+
+// Volume flags and percent in use are left out of the checksum, so that
+// they can be updated without rewriting the checksum sector.
+fn calc_boot_checksum(buf: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for (i, x) in buf.iter().enumerate() {
+        if i == 106 || i == 107 || i == 112 {
+            continue;
+        }
+        sum = sum.rotate_right(1).wrapping_add(u32::from(*x));
+    }
+    sum
+}
+
+fn checksumck(name: &str, buf: &[u8], sector_size: u64) -> exfat_utils::Result<()> {
+    let n = usize::try_from(CHECKSUM_SECTOR * sector_size)?;
+    let actual = calc_boot_checksum(&buf[..n]);
+    let stored: Vec<u32> = buf[n..]
+        .chunks_exact(4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .collect();
+    let bad = stored.iter().filter(|x| **x != actual).count();
+    if bad == 0 {
+        println!("{name} checksum {actual:#010x} verified");
+    } else {
+        println!(
+            "{name} checksum mismatch, calculated {actual:#010x}, \
+            stored {:#010x}, {bad} of {} entries differ",
+            stored[0],
+            stored.len()
+        );
+    }
+    Ok(())
+}
+
+fn get_fields(sb: &libexfat::fs::ExfatSuperBlock) -> Vec<(&'static str, String, bool)> {
+    vec![
+        ("jump", format!("{:02x?}", sb.jump), false),
+        (
+            "oem_name",
+            String::from_utf8_lossy(&sb.oem_name).to_string(),
+            false,
+        ),
+        (
+            "sector_start",
+            u64::from_le(sb.sector_start).to_string(),
+            false,
+        ),
+        (
+            "sector_count",
+            u64::from_le(sb.sector_count).to_string(),
+            false,
+        ),
+        (
+            "fat_sector_start",
+            u32::from_le(sb.fat_sector_start).to_string(),
+            false,
+        ),
+        (
+            "fat_sector_count",
+            u32::from_le(sb.fat_sector_count).to_string(),
+            false,
+        ),
+        (
+            "cluster_sector_start",
+            u32::from_le(sb.cluster_sector_start).to_string(),
+            false,
+        ),
+        (
+            "cluster_count",
+            u32::from_le(sb.cluster_count).to_string(),
+            false,
+        ),
+        (
+            "rootdir_cluster",
+            u32::from_le(sb.rootdir_cluster).to_string(),
+            false,
+        ),
+        (
+            "volume_serial",
+            format!("{:#010x}", u32::from_le(sb.volume_serial)),
+            false,
+        ),
+        (
+            "version",
+            format!("{}.{}", sb.version_major, sb.version_minor),
+            false,
+        ),
+        (
+            "volume_state",
+            format!("{:#06x}", u16::from_le(sb.volume_state)),
+            true,
+        ),
+        ("sector_bits", sb.sector_bits.to_string(), false),
+        ("spc_bits", sb.spc_bits.to_string(), false),
+        ("fat_count", sb.fat_count.to_string(), false),
+        ("drive_no", format!("{:#04x}", sb.drive_no), false),
+        ("allocated_percent", sb.allocated_percent.to_string(), true),
+        (
+            "boot_signature",
+            format!("{:#06x}", u16::from_le(sb.boot_signature)),
+            false,
+        ),
+    ]
+}
+
+// Only volume flags and percent in use may be stale in the backup boot
+// region, anything else differing means one of the regions is damaged.
+pub(crate) fn compare_boot_regions(spec: &str) -> exfat_utils::Result<()> {
+    let mut dev = libexfat::open(spec, "ro")?;
+    let main = exfat_utils::volume::read_super_block(&mut dev, 0)?;
+    if !(9..=12).contains(&main.sector_bits) {
+        log::error!(
+            "invalid sector size 2^{} in main boot region",
+            main.sector_bits
+        );
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+    let sector_size = main.get_sector_size();
+    let size = BOOT_REGION_SECTORS * sector_size;
+    let backup = exfat_utils::volume::read_super_block(&mut dev, size)?;
+
+    let main_buf = dev.preadx(size, 0)?;
+    let backup_buf = dev.preadx(size, size)?;
+    checksumck("main boot region", &main_buf, sector_size)?;
+    checksumck("backup boot region", &backup_buf, sector_size)?;
+
+    println!();
+    println!("{:<24}{:>20}{:>20}", "Field", "Main", "Backup");
+    let mut count = 0;
+    for ((name, a, allowed), (_, b, _)) in get_fields(&main).into_iter().zip(get_fields(&backup)) {
+        let status = if a == b {
+            ""
+        } else if allowed {
+            "  differs, allowed"
+        } else {
+            count += 1;
+            "  DIFFERS"
+        };
+        println!("{name:<24}{a:>20}{b:>20}{status}");
+    }
+    // boot code, extended boot sectors and OEM parameters
+    let sb_size = usize::try_from(libexfat::fs::EXFAT_SUPER_BLOCK_SIZE_U64)?;
+    let n = usize::try_from(CHECKSUM_SECTOR * sector_size)?;
+    let same = main.unused1 == backup.unused1
+        && main.unused2 == backup.unused2
+        && main_buf[sb_size..n] == backup_buf[sb_size..n];
+    println!(
+        "{:<24}{:>40}{}",
+        "boot_code_and_sectors",
+        if same { "same" } else { "different" },
+        if same { "" } else { "  DIFFERS" }
+    );
+    if !same {
+        count += 1;
+    }
+
+    println!();
+    if count == 0 {
+        println!("Boot regions match");
+    } else {
+        println!("Boot regions differ in {count} fields which must not differ");
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+fn get_fat_entry_string(value: u32) -> String {
+    match value {
+        libexfat::fs::EXFAT_CLUSTER_END => format!("{value:#010x} (end)"),
+        libexfat::fs::EXFAT_CLUSTER_BAD => format!("{value:#010x} (bad)"),
+        libexfat::fs::EXFAT_CLUSTER_FREE => format!("{value:#010x} (free)"),
+        _ => format!("{value:#010x}"),
+    }
+}
+
+fn print_cluster(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    index: u64,
+    cluster: u32,
+    is_contiguous: bool,
+) -> exfat_utils::Result<u32> {
+    let value = vol.get_fat_entry(cluster)?;
+    let mut notes = vec![];
+    if is_contiguous {
+        notes.push("ignored");
+    }
+    if !ef.is_cluster_allocated((cluster - libexfat::fs::EXFAT_FIRST_DATA_CLUSTER).try_into()?)? {
+        notes.push("not allocated in bitmap");
+    }
+    println!(
+        "{index:>10} {cluster:#010x} {}{}",
+        get_fat_entry_string(value),
+        if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", notes.join(", "))
+        }
+    );
+    Ok(value)
+}
+
+// The chain is followed with the FAT as is, so that broken chains are
+// printed up to where they break rather than rejected.
+fn print_node_chain(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    nid: libexfat::node::Nid,
+    path: &str,
+) -> exfat_utils::Result<()> {
+    let node = exfat_utils::util::get_node!(ef, nid);
+    let mut cluster = node.get_start_cluster();
+    let size = node.get_size();
+    let is_contiguous = node.get_is_contiguous();
+    let clusters = libexfat::div_round_up!(size, vol.get_cluster_size());
+    println!(
+        "'{path}': {size} bytes, {clusters} clusters, {}",
+        if is_contiguous {
+            "NoFatChain, FAT entries are ignored"
+        } else {
+            "FAT chain"
+        }
+    );
+    println!("{:>10} {:<10} FAT entry", "index", "cluster");
+
+    if cluster == 0 {
+        return Ok(());
+    }
+    for i in 0..clusters {
+        if vol.cluster_invalid(cluster) {
+            println!("{i:>10} {cluster:#010x} [invalid cluster, chain is broken]");
+            return Ok(());
+        }
+        let value = print_cluster(ef, vol, i, cluster, is_contiguous)?;
+        if is_contiguous {
+            cluster += 1;
+        } else if value == libexfat::fs::EXFAT_CLUSTER_END {
+            if i + 1 != clusters {
+                println!("chain ends at {} of {clusters} clusters", i + 1);
+            }
+            return Ok(());
+        } else {
+            cluster = value;
+        }
+    }
+    if !is_contiguous {
+        println!("chain continues past the file size to {cluster:#010x}");
+    }
+    Ok(())
+}
+
+pub(crate) fn print_chain(spec: &str, path: &str) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let nid = crate::lookup(&mut ef, path)?;
+    let result = print_node_chain(&mut ef, &mut vol, nid, path);
+    exfat_utils::util::get_node_mut!(ef, nid).put();
+    result
+}
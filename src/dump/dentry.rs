@@ -0,0 +1,308 @@
+fn print_field<T: std::fmt::Display>(name: &str, value: T) {
+    println!("  {name:<20} {value}");
+}
+
+fn get_type_name(typ: u8) -> String {
+    // deleted entries keep their type code with the in-use bit cleared
+    match typ | libexfat::fs::EXFAT_ENTRY_VALID {
+        libexfat::fs::EXFAT_ENTRY_BITMAP => "allocation bitmap".to_string(),
+        libexfat::fs::EXFAT_ENTRY_UPCASE => "upcase table".to_string(),
+        libexfat::fs::EXFAT_ENTRY_LABEL => "volume label".to_string(),
+        libexfat::fs::EXFAT_ENTRY_FILE => "file".to_string(),
+        exfat_utils::entry::EXFAT_ENTRY_GUID => "volume GUID".to_string(),
+        libexfat::fs::EXFAT_ENTRY_FILE_INFO => "stream extension".to_string(),
+        libexfat::fs::EXFAT_ENTRY_FILE_NAME => "file name".to_string(),
+        exfat_utils::entry::EXFAT_ENTRY_VENDOR_EXT => "vendor extension".to_string(),
+        exfat_utils::entry::EXFAT_ENTRY_VENDOR_ALLOC => "vendor allocation".to_string(),
+        x => format!(
+            "unknown {} {}",
+            if x & libexfat::fs::EXFAT_ENTRY_OPTIONAL == 0 {
+                "critical"
+            } else {
+                "benign"
+            },
+            if x & libexfat::fs::EXFAT_ENTRY_CONTINUED == 0 {
+                "primary"
+            } else {
+                "secondary"
+            }
+        ),
+    }
+}
+
+fn get_chars(slot: &exfat_utils::entry::Slot, offset: usize, count: usize) -> String {
+    let chars: Vec<u16> = (0..count).map(|i| slot.get_u16(offset + i * 2)).collect();
+    String::from_utf16_lossy(&chars[..libexfat::utf::utf16_length(&chars)])
+}
+
+// GUIDs are stored with the first three fields in little endian
+fn get_guid(slot: &exfat_utils::entry::Slot, offset: usize) -> String {
+    let b = &slot.as_bytes()[offset..offset + 16];
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        slot.get_u32(offset),
+        slot.get_u16(offset + 4),
+        slot.get_u16(offset + 6),
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15]
+    )
+}
+
+fn print_allocation(slot: &exfat_utils::entry::Slot, start_cluster: usize, size: usize) {
+    print_field(
+        "Start cluster",
+        format!("{:#x}", slot.get_u32(start_cluster)),
+    );
+    print_field("Data length", slot.get_u64(size));
+}
+
+fn print_set_checksum(slots: &[exfat_utils::entry::Slot], i: usize, offset: usize) {
+    let checksum = slots[i].get_u16(offset);
+    let count = usize::from(slots[i].get_u8(exfat_utils::entry::FILE_SECONDARY_COUNT));
+    let Some(set) = slots.get(i..=i + count) else {
+        print_field("Set checksum", format!("{checksum:#06x} (set truncated)"));
+        return;
+    };
+    // deleting a set clears the in-use bit of every type byte the checksum
+    // covers, so it is verified with the bits restored
+    let deleted = slots[i].get_type() & libexfat::fs::EXFAT_ENTRY_VALID == 0;
+    let actual = if deleted {
+        let set: Vec<exfat_utils::entry::Slot> = set
+            .iter()
+            .map(|x| {
+                let mut x = *x;
+                x.set_u8(0, x.get_type() | libexfat::fs::EXFAT_ENTRY_VALID);
+                x
+            })
+            .collect();
+        exfat_utils::entry::calc_set_checksum(&set)
+    } else {
+        exfat_utils::entry::calc_set_checksum(set)
+    };
+    if checksum == actual {
+        print_field(
+            "Set checksum",
+            format!(
+                "{checksum:#06x} (verified{})",
+                if deleted { " as in use" } else { "" }
+            ),
+        );
+    } else {
+        print_field(
+            "Set checksum",
+            format!("{checksum:#06x} (mismatch, calculated {actual:#06x})"),
+        );
+    }
+}
+
+fn print_timestamp(slot: &exfat_utils::entry::Slot, name: &str, time: usize, cs: u8, tzo: usize) {
+    print_field(
+        name,
+        exfat_utils::entry::format_timestamp(slot.get_u32(time), cs, slot.get_u8(tzo)),
+    );
+}
+
+fn print_file(slots: &[exfat_utils::entry::Slot], i: usize) {
+    let slot = &slots[i];
+    print_field(
+        "Secondary count",
+        slot.get_u8(exfat_utils::entry::FILE_SECONDARY_COUNT),
+    );
+    print_set_checksum(slots, i, exfat_utils::entry::FILE_CHECKSUM);
+    print_field(
+        "Attributes",
+        format!("{:#06x}", slot.get_u16(exfat_utils::entry::FILE_ATTRIB)),
+    );
+    print_timestamp(
+        slot,
+        "Create time",
+        exfat_utils::entry::FILE_CRTIME,
+        slot.get_u8(exfat_utils::entry::FILE_CRTIME_CS),
+        exfat_utils::entry::FILE_CRTIME_TZO,
+    );
+    print_timestamp(
+        slot,
+        "Modify time",
+        exfat_utils::entry::FILE_MTIME,
+        slot.get_u8(exfat_utils::entry::FILE_MTIME_CS),
+        exfat_utils::entry::FILE_MTIME_TZO,
+    );
+    print_timestamp(
+        slot,
+        "Access time",
+        exfat_utils::entry::FILE_ATIME,
+        0,
+        exfat_utils::entry::FILE_ATIME_TZO,
+    );
+}
+
+fn print_stream(slot: &exfat_utils::entry::Slot) {
+    let flags = slot.get_u8(exfat_utils::entry::STREAM_FLAGS);
+    print_field(
+        "Flags",
+        format!(
+            "{flags:#04x}{}{}",
+            if flags & 0x01 != 0 {
+                " AllocationPossible"
+            } else {
+                ""
+            },
+            if flags & 0x02 != 0 { " NoFatChain" } else { "" }
+        ),
+    );
+    print_field(
+        "Name length",
+        slot.get_u8(exfat_utils::entry::STREAM_NAME_LENGTH),
+    );
+    print_field(
+        "Name hash",
+        format!(
+            "{:#06x}",
+            slot.get_u16(exfat_utils::entry::STREAM_NAME_HASH)
+        ),
+    );
+    print_field(
+        "Valid data length",
+        slot.get_u64(exfat_utils::entry::STREAM_VALID_SIZE),
+    );
+    print_allocation(
+        slot,
+        exfat_utils::entry::STREAM_START_CLUSTER,
+        exfat_utils::entry::STREAM_SIZE,
+    );
+}
+
+fn print_slot(slots: &[exfat_utils::entry::Slot], i: usize) {
+    let slot = &slots[i];
+    let typ = slot.get_type();
+    println!(
+        "Slot {i} (cluster {:#x}, offset {:#x})",
+        slot.get_cluster(),
+        slot.get_offset()
+    );
+    for x in exfat_utils::util::hexdump(slot.as_bytes(), slot.get_offset()) {
+        println!("{x}");
+    }
+
+    if slot.is_eod() {
+        print_field("Type", format!("{typ:#04x} (unused)"));
+        return;
+    }
+    print_field(
+        "Type",
+        format!(
+            "{typ:#04x} ({}{})",
+            get_type_name(typ),
+            if typ & libexfat::fs::EXFAT_ENTRY_VALID == 0 {
+                ", deleted"
+            } else {
+                ""
+            }
+        ),
+    );
+    match typ | libexfat::fs::EXFAT_ENTRY_VALID {
+        libexfat::fs::EXFAT_ENTRY_BITMAP => {
+            print_field("Flags", slot.get_u8(exfat_utils::entry::BITMAP_FLAGS));
+            print_allocation(
+                slot,
+                exfat_utils::entry::BITMAP_START_CLUSTER,
+                exfat_utils::entry::BITMAP_SIZE,
+            );
+        }
+        libexfat::fs::EXFAT_ENTRY_UPCASE => {
+            print_field(
+                "Table checksum",
+                format!(
+                    "{:#010x}",
+                    slot.get_u32(exfat_utils::entry::UPCASE_CHECKSUM)
+                ),
+            );
+            print_allocation(
+                slot,
+                exfat_utils::entry::UPCASE_START_CLUSTER,
+                exfat_utils::entry::UPCASE_SIZE,
+            );
+        }
+        libexfat::fs::EXFAT_ENTRY_LABEL => {
+            let length = slot.get_u8(exfat_utils::entry::LABEL_LENGTH);
+            print_field("Length", length);
+            print_field(
+                "Label",
+                get_chars(
+                    slot,
+                    exfat_utils::entry::LABEL_CHARS,
+                    std::cmp::min(usize::from(length), exfat_utils::entry::LABEL_CHARS_MAX),
+                ),
+            );
+        }
+        libexfat::fs::EXFAT_ENTRY_FILE => print_file(slots, i),
+        exfat_utils::entry::EXFAT_ENTRY_GUID => {
+            print_field(
+                "Secondary count",
+                slot.get_u8(exfat_utils::entry::GUID_SECONDARY_COUNT),
+            );
+            print_set_checksum(slots, i, exfat_utils::entry::GUID_CHECKSUM);
+            print_field(
+                "Flags",
+                format!("{:#06x}", slot.get_u16(exfat_utils::entry::GUID_FLAGS)),
+            );
+            print_field("GUID", get_guid(slot, exfat_utils::entry::GUID_GUID));
+        }
+        libexfat::fs::EXFAT_ENTRY_FILE_INFO => print_stream(slot),
+        libexfat::fs::EXFAT_ENTRY_FILE_NAME => {
+            print_field(
+                "Name",
+                get_chars(
+                    slot,
+                    exfat_utils::entry::NAME_CHARS,
+                    exfat_utils::entry::NAME_CHARS_PER_ENTRY,
+                ),
+            );
+        }
+        exfat_utils::entry::EXFAT_ENTRY_VENDOR_EXT => {
+            print_field("Flags", slot.get_u8(exfat_utils::entry::VENDOR_FLAGS));
+            print_field("GUID", get_guid(slot, exfat_utils::entry::VENDOR_GUID));
+        }
+        exfat_utils::entry::EXFAT_ENTRY_VENDOR_ALLOC => {
+            print_field("Flags", slot.get_u8(exfat_utils::entry::VENDOR_FLAGS));
+            print_field("GUID", get_guid(slot, exfat_utils::entry::VENDOR_GUID));
+            print_allocation(
+                slot,
+                exfat_utils::entry::VENDOR_START_CLUSTER,
+                exfat_utils::entry::VENDOR_SIZE,
+            );
+        }
+        _ => (),
+    }
+}
+
+pub(crate) fn dump_entries(spec: &str, path: &str) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let nid = crate::lookup(&mut ef, path)?;
+    let node = exfat_utils::util::get_node!(ef, nid);
+    let is_directory = node.is_directory();
+    let start_cluster = node.get_start_cluster();
+    let is_contiguous = node.get_is_contiguous();
+    let size = node.get_size();
+    exfat_utils::util::get_node_mut!(ef, nid).put();
+    if !is_directory {
+        log::error!("'{path}' is not a directory");
+        return Err(Box::new(nix::errno::Errno::ENOTDIR));
+    }
+
+    // slots past the end of directory marker are dumped as well
+    let slots = vol.read_directory(start_cluster, is_contiguous, Some(size))?;
+    for i in 0..slots.len() {
+        if i != 0 {
+            println!();
+        }
+        print_slot(&slots, i);
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+fn find_special_entry(
+    vol: &mut exfat_utils::volume::Volume,
+    typ: u8,
+) -> exfat_utils::Result<exfat_utils::entry::Slot> {
+    let Some(slot) = vol.find_root_entry(typ)? else {
+        log::error!("root directory has no entry of type {typ:#04x}");
+        return Err(Box::new(nix::errno::Errno::ENOENT));
+    };
+    Ok(slot)
+}
+
+fn read_object(
+    vol: &mut exfat_utils::volume::Volume,
+    slot: &exfat_utils::entry::Slot,
+    start_cluster: usize,
+    size: usize,
+) -> exfat_utils::Result<Vec<u8>> {
+    let size = slot.get_u64(size);
+    let clusters = vol.get_cluster_chain(slot.get_u32(start_cluster), false, Some(size))?;
+    let mut buf = vol.read_clusters(&clusters)?;
+    buf.truncate(usize::try_from(size)?);
+    Ok(buf)
+}
+
+fn open_output(f: &str) -> exfat_utils::Result<Box<dyn std::io::Write>> {
+    Ok(if f == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(std::fs::File::create(f)?)
+    })
+}
+
+// Runs are printed in cluster numbers, bits past the cluster count are
+// padding and ignored.
+fn write_bitmap_runs(
+    w: &mut dyn std::io::Write,
+    buf: &[u8],
+    cluster_count: u32,
+) -> exfat_utils::Result<()> {
+    let is_set = |i: u32| buf[usize::try_from(i / 8).unwrap()] & (1 << (i % 8)) != 0;
+    let mut start = 0;
+    while start < cluster_count {
+        let allocated = is_set(start);
+        let mut end = start + 1;
+        while end < cluster_count && is_set(end) == allocated {
+            end += 1;
+        }
+        writeln!(
+            w,
+            "{} {:#x}-{:#x} ({} clusters)",
+            if allocated { "allocated" } else { "free" },
+            start + libexfat::fs::EXFAT_FIRST_DATA_CLUSTER,
+            end - 1 + libexfat::fs::EXFAT_FIRST_DATA_CLUSTER,
+            end - start
+        )?;
+        start = end;
+    }
+    Ok(())
+}
+
+pub(crate) fn export_bitmap(spec: &str, f: &str, readable: bool) -> exfat_utils::Result<()> {
+    let mut vol = exfat_utils::volume::Volume::open(spec, "ro")?;
+    let slot = find_special_entry(&mut vol, libexfat::fs::EXFAT_ENTRY_BITMAP)?;
+    let buf = read_object(
+        &mut vol,
+        &slot,
+        exfat_utils::entry::BITMAP_START_CLUSTER,
+        exfat_utils::entry::BITMAP_SIZE,
+    )?;
+    let cluster_count = vol.get_cluster_count();
+    if u64::try_from(buf.len())? * 8 < u64::from(cluster_count) {
+        log::error!(
+            "allocation bitmap of {} bytes is too small for {cluster_count} clusters",
+            buf.len()
+        );
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+
+    let mut w = open_output(f)?;
+    if readable {
+        write_bitmap_runs(&mut w, &buf, cluster_count)?;
+    } else {
+        w.write_all(&buf)?;
+    }
+    Ok(w.flush()?)
+}
+
+// The raw export is the decompressed table, 2 bytes in little endian for
+// each code point.
+pub(crate) fn export_upcase(spec: &str, f: &str, readable: bool) -> exfat_utils::Result<()> {
+    let mut vol = exfat_utils::volume::Volume::open(spec, "ro")?;
+    let slot = find_special_entry(&mut vol, libexfat::fs::EXFAT_ENTRY_UPCASE)?;
+    let buf = read_object(
+        &mut vol,
+        &slot,
+        exfat_utils::entry::UPCASE_START_CLUSTER,
+        exfat_utils::entry::UPCASE_SIZE,
+    )?;
+    let table = exfat_utils::upcase::decompress(&buf)?;
+
+    let mut w = open_output(f)?;
+    if readable {
+        let checksum = slot.get_u32(exfat_utils::entry::UPCASE_CHECKSUM);
+        let actual = exfat_utils::upcase::calc_checksum(&buf);
+        writeln!(
+            w,
+            "checksum {checksum:#010x} ({})",
+            if checksum == actual {
+                "verified".to_string()
+            } else {
+                format!("mismatch, calculated {actual:#010x}")
+            }
+        )?;
+        for (c, x) in (0..=u16::MAX).zip(&table) {
+            if c != *x {
+                writeln!(w, "U+{c:04X} -> U+{x:04X}")?;
+            }
+        }
+    } else {
+        let v: Vec<u8> = table.iter().flat_map(|x| x.to_le_bytes()).collect();
+        w.write_all(&v)?;
+    }
+    Ok(w.flush()?)
+}
@@ -0,0 +1,170 @@
+#[derive(Debug)]
+struct FileFragments {
+    path: String,
+    fragments: u64,
+    clusters: u64,
+    is_contiguous: bool,
+}
+
+// Power of two buckets, index n counts values in 2^n..2^(n+1).
+#[derive(Debug, Default)]
+struct Histogram(Vec<u64>);
+
+impl Histogram {
+    fn add(&mut self, value: u64) {
+        let i = usize::try_from(value.ilog2()).unwrap();
+        if self.0.len() <= i {
+            self.0.resize(i + 1, 0);
+        }
+        self.0[i] += 1;
+    }
+
+    fn print(&self, unit: &str) {
+        for (i, x) in self.0.iter().enumerate() {
+            let low = 1u64 << i;
+            let range = if i == 0 {
+                format!("{low} {unit}")
+            } else {
+                format!("{low}-{} {unit}", (low << 1) - 1)
+            };
+            println!("  {range:<26}{x:>10}");
+        }
+    }
+}
+
+fn get_file_fragments(
+    vol: &mut exfat_utils::volume::Volume,
+    node: &libexfat::node::Node,
+    path: String,
+) -> exfat_utils::Result<FileFragments> {
+    let chain = vol.get_cluster_chain(
+        node.get_start_cluster(),
+        node.get_is_contiguous(),
+        Some(node.get_size()),
+    )?;
+    let breaks = chain.windows(2).filter(|x| x[1] != x[0] + 1).count();
+    Ok(FileFragments {
+        path,
+        fragments: u64::try_from(breaks)? + 1,
+        clusters: u64::try_from(chain.len())?,
+        is_contiguous: node.get_is_contiguous(),
+    })
+}
+
+// Files without clusters allocated have no fragments and are skipped.
+// Files with broken cluster chains are counted, but don't stop the report.
+fn collect_fragments(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    dnid: libexfat::node::Nid,
+    files: &mut Vec<FileFragments>,
+    broken: &mut u64,
+) -> exfat_utils::Result<()> {
+    exfat_utils::util::walk_tree(ef, dnid, "/", |ef, nid, path, visit| {
+        let node = exfat_utils::util::get_node!(ef, nid);
+        if visit == exfat_utils::util::Visit::Entry && node.get_start_cluster() != 0 {
+            match get_file_fragments(vol, node, path.to_string()) {
+                Ok(v) => files.push(v),
+                Err(e) => {
+                    log::warn!("skipping '{path}' with broken cluster chain: {e}");
+                    *broken += 1;
+                }
+            }
+        }
+        Ok(true)
+    })
+}
+
+// Runs of clear bits in the allocation bitmap.
+fn get_free_extents(
+    ef: &mut libexfat::exfat::Exfat,
+    cluster_count: u32,
+) -> exfat_utils::Result<Histogram> {
+    let mut histogram = Histogram::default();
+    let mut run = 0;
+    for i in 0..cluster_count {
+        if ef.is_cluster_allocated(i.try_into()?)? {
+            if run != 0 {
+                histogram.add(run);
+            }
+            run = 0;
+        } else {
+            run += 1;
+        }
+    }
+    if run != 0 {
+        histogram.add(run);
+    }
+    Ok(histogram)
+}
+
+// percentage with one decimal digit without going through floats
+fn get_percent(n: u64, total: u64) -> String {
+    if total == 0 {
+        return "0.0%".to_string();
+    }
+    let x = n * 1000 / total;
+    format!("{}.{}%", x / 10, x % 10)
+}
+
+pub(crate) fn report(spec: &str, top: usize) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let mut files = vec![];
+    let mut broken = 0;
+    let nid = crate::lookup(&mut ef, "/")?;
+    let result = collect_fragments(&mut ef, &mut vol, nid, &mut files, &mut broken);
+    exfat_utils::util::get_node_mut!(ef, nid).put();
+    result?;
+    let free_extents = get_free_extents(&mut ef, vol.get_cluster_count())?;
+
+    let total = u64::try_from(files.len())?;
+    let contiguous = u64::try_from(files.iter().filter(|x| x.is_contiguous).count())?;
+    let fragmented = u64::try_from(files.iter().filter(|x| x.fragments > 1).count())?;
+    let fragments: u64 = files.iter().map(|x| x.fragments).sum();
+    println!("Files                     {total:>10}");
+    println!(
+        "Contiguous (NoFatChain)   {contiguous:>10} {}",
+        get_percent(contiguous, total)
+    );
+    println!(
+        "Chained                   {:>10} {}",
+        total - contiguous,
+        get_percent(total - contiguous, total)
+    );
+    println!(
+        "Fragmented                {fragmented:>10} {}",
+        get_percent(fragmented, total)
+    );
+    println!("Fragments                 {fragments:>10}");
+    println!("Broken chain              {broken:>10}");
+
+    println!();
+    println!("Fragments per file");
+    let mut histogram = Histogram::default();
+    for x in &files {
+        histogram.add(x.fragments);
+    }
+    histogram.print("fragments");
+
+    // 0 lists every file rather than only the most fragmented ones
+    println!();
+    if top == 0 {
+        println!("Fragments of files");
+    } else {
+        println!("Most fragmented files");
+    }
+    println!("  {:>10} {:>10} path", "fragments", "clusters");
+    files.sort_by(|a, b| b.fragments.cmp(&a.fragments).then(a.path.cmp(&b.path)));
+    for x in files
+        .iter()
+        .filter(|x| top == 0 || x.fragments > 1)
+        .take(if top == 0 { usize::MAX } else { top })
+    {
+        println!("  {:>10} {:>10} {}", x.fragments, x.clusters, x.path);
+    }
+
+    println!();
+    println!("Free extents");
+    free_extents.print("clusters");
+    Ok(())
+}
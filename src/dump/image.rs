@@ -0,0 +1,177 @@
+use std::os::unix::fs::FileExt;
+
+const READ_SIZE_MAX: u64 = 1 << 20;
+const SCRAMBLE_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn copy_range(
+    vol: &mut exfat_utils::volume::Volume,
+    image: &std::fs::File,
+    offset: u64,
+    size: u64,
+) -> exfat_utils::Result<()> {
+    let mut x = 0;
+    while x < size {
+        let n = std::cmp::min(READ_SIZE_MAX, size - x);
+        image.write_all_at(&vol.pread(n, offset + x)?, offset + x)?;
+        x += n;
+    }
+    Ok(())
+}
+
+fn copy_clusters(
+    vol: &mut exfat_utils::volume::Volume,
+    image: &std::fs::File,
+    clusters: &[u32],
+) -> exfat_utils::Result<()> {
+    for c in clusters {
+        copy_range(vol, image, vol.c2o(*c), vol.get_cluster_size())?;
+    }
+    Ok(())
+}
+
+// Start cluster, NoFatChain flag and size of every directory including
+// the root directory.
+fn collect_directories(
+    ef: &mut libexfat::exfat::Exfat,
+    dnid: libexfat::node::Nid,
+    dirs: &mut Vec<(u32, bool, u64)>,
+) -> exfat_utils::Result<()> {
+    exfat_utils::util::walk_tree(ef, dnid, "/", |ef, nid, _, visit| {
+        if visit == exfat_utils::util::Visit::Directory {
+            let dnode = exfat_utils::util::get_node!(ef, nid);
+            dirs.push((
+                dnode.get_start_cluster(),
+                dnode.get_is_contiguous(),
+                dnode.get_size(),
+            ));
+        }
+        Ok(true)
+    })
+}
+
+// Names are replaced with the index of the entry set within the directory,
+// which keeps them unique, in the original length as long as it fits.
+fn get_scrambled_name(index: usize, length: usize) -> Vec<u16> {
+    let mut name = vec![];
+    let mut x = index;
+    while x != 0 || name.len() < std::cmp::max(length, 1) {
+        name.push(u16::from(SCRAMBLE_CHARS[x % SCRAMBLE_CHARS.len()]));
+        x /= SCRAMBLE_CHARS.len();
+    }
+    name.reverse();
+    name
+}
+
+// Names of deleted entries are cleared, they aren't part of any entry
+// set to keep consistent.
+fn scramble_directory(slots: &mut [exfat_utils::entry::Slot], upcase: &[u16]) {
+    let deleted_name = libexfat::fs::EXFAT_ENTRY_FILE_NAME & !libexfat::fs::EXFAT_ENTRY_VALID;
+    for slot in slots.iter_mut() {
+        if slot.get_type() == deleted_name {
+            for i in 0..exfat_utils::entry::NAME_CHARS_PER_ENTRY {
+                slot.set_u16(exfat_utils::entry::NAME_CHARS + i * 2, 0);
+            }
+        }
+    }
+
+    let index: std::collections::HashMap<u64, usize> = slots
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (x.get_offset(), i))
+        .collect();
+    for (i, mut es) in exfat_utils::entry::get_entry_sets(slots)
+        .into_iter()
+        .enumerate()
+    {
+        let limit = std::cmp::min(
+            es.get_name_entries_count() * exfat_utils::entry::NAME_CHARS_PER_ENTRY,
+            libexfat::fs::EXFAT_NAME_MAX,
+        );
+        let name = get_scrambled_name(i, es.get_name().len());
+        if name.len() > limit {
+            continue;
+        }
+        es.set_name(&name);
+        let hash = exfat_utils::entry::calc_name_hash(upcase, &name);
+        if let Some(x) = es.get_stream_mut() {
+            x.set_u16(exfat_utils::entry::STREAM_NAME_HASH, hash);
+        }
+        es.update_checksum();
+        for x in es.get_slots() {
+            slots[index[&x.get_offset()]] = *x;
+        }
+    }
+}
+
+fn read_upcase_table(
+    vol: &mut exfat_utils::volume::Volume,
+    slot: &exfat_utils::entry::Slot,
+    clusters: &[u32],
+) -> exfat_utils::Result<Vec<u16>> {
+    let mut buf = vol.read_clusters(clusters)?;
+    buf.truncate(usize::try_from(
+        slot.get_u64(exfat_utils::entry::UPCASE_SIZE),
+    )?);
+    exfat_utils::upcase::decompress(&buf)
+}
+
+pub(crate) fn write_image(spec: &str, f: &str, scramble: bool) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let sb = *vol.get_super_block();
+    let sector_size = vol.get_sector_size();
+    let image = std::fs::File::create(f)?;
+    // file data is left as holes
+    image.set_len(u64::from_le(sb.sector_count) * sector_size)?;
+
+    // main and backup boot regions, then FATs, TexFAT has a second one
+    copy_range(&mut vol, &image, 0, 24 * sector_size)?;
+    copy_range(
+        &mut vol,
+        &image,
+        u64::from(u32::from_le(sb.fat_sector_start)) * sector_size,
+        u64::from(u32::from_le(sb.fat_sector_count)) * u64::from(sb.fat_count) * sector_size,
+    )?;
+
+    let mut upcase = exfat_utils::upcase::decompress(&exfat_utils::uctc::UPCASE_TABLE)?;
+    for (typ, start_cluster, size) in [
+        (
+            libexfat::fs::EXFAT_ENTRY_BITMAP,
+            exfat_utils::entry::BITMAP_START_CLUSTER,
+            exfat_utils::entry::BITMAP_SIZE,
+        ),
+        (
+            libexfat::fs::EXFAT_ENTRY_UPCASE,
+            exfat_utils::entry::UPCASE_START_CLUSTER,
+            exfat_utils::entry::UPCASE_SIZE,
+        ),
+    ] {
+        let Some(slot) = vol.find_root_entry(typ)? else {
+            continue;
+        };
+        let clusters =
+            vol.get_cluster_chain(slot.get_u32(start_cluster), false, Some(slot.get_u64(size)))?;
+        copy_clusters(&mut vol, &image, &clusters)?;
+        if typ == libexfat::fs::EXFAT_ENTRY_UPCASE {
+            match read_upcase_table(&mut vol, &slot, &clusters) {
+                Ok(v) => upcase = v,
+                Err(e) => log::warn!("using the standard upcase table: {e}"),
+            }
+        }
+    }
+
+    let mut dirs = vec![];
+    let nid = crate::lookup(&mut ef, "/")?;
+    let result = collect_directories(&mut ef, nid, &mut dirs);
+    exfat_utils::util::get_node_mut!(ef, nid).put();
+    result?;
+    for (start_cluster, is_contiguous, size) in dirs {
+        let mut slots = vol.read_directory(start_cluster, is_contiguous, Some(size))?;
+        if scramble {
+            scramble_directory(&mut slots, &upcase);
+        }
+        for slot in slots {
+            image.write_all_at(slot.as_bytes(), slot.get_offset())?;
+        }
+    }
+    Ok(image.sync_all()?)
+}
@@ -0,0 +1,132 @@
+fn get_attrib_string(attrib: u16) -> String {
+    [
+        (libexfat::fs::EXFAT_ATTRIB_DIR, 'd'),
+        (libexfat::fs::EXFAT_ATTRIB_RO, 'r'),
+        (libexfat::fs::EXFAT_ATTRIB_HIDDEN, 'h'),
+        (libexfat::fs::EXFAT_ATTRIB_SYSTEM, 's'),
+        (libexfat::fs::EXFAT_ATTRIB_ARCH, 'a'),
+    ]
+    .iter()
+    .map(|(x, c)| if attrib & x != 0 { *c } else { '-' })
+    .collect()
+}
+
+// libexfat doesn't expose valid data length and timestamps, those are
+// taken from raw entry sets of the directory
+fn get_entry_sets(
+    vol: &mut exfat_utils::volume::Volume,
+    dnode: &libexfat::node::Node,
+) -> exfat_utils::Result<std::collections::HashMap<String, exfat_utils::entry::EntrySet>> {
+    let slots = vol.read_directory(
+        dnode.get_start_cluster(),
+        dnode.get_is_contiguous(),
+        Some(dnode.get_size()),
+    )?;
+    Ok(exfat_utils::entry::get_entry_sets(&slots)
+        .into_iter()
+        .map(|x| (String::from_utf16_lossy(&x.get_name()), x))
+        .collect())
+}
+
+fn print_node(node: &libexfat::node::Node, es: Option<&exfat_utils::entry::EntrySet>) {
+    let valid_size = es
+        .and_then(exfat_utils::entry::EntrySet::get_stream)
+        .map_or("-".to_string(), |x| {
+            x.get_u64(exfat_utils::entry::STREAM_VALID_SIZE).to_string()
+        });
+    let timestamps = match es {
+        Some(v) => {
+            let file = v.get_file();
+            [
+                exfat_utils::entry::format_timestamp(
+                    file.get_u32(exfat_utils::entry::FILE_CRTIME),
+                    file.get_u8(exfat_utils::entry::FILE_CRTIME_CS),
+                    file.get_u8(exfat_utils::entry::FILE_CRTIME_TZO),
+                ),
+                exfat_utils::entry::format_timestamp(
+                    file.get_u32(exfat_utils::entry::FILE_MTIME),
+                    file.get_u8(exfat_utils::entry::FILE_MTIME_CS),
+                    file.get_u8(exfat_utils::entry::FILE_MTIME_TZO),
+                ),
+                exfat_utils::entry::format_timestamp(
+                    file.get_u32(exfat_utils::entry::FILE_ATIME),
+                    0,
+                    file.get_u8(exfat_utils::entry::FILE_ATIME_TZO),
+                ),
+            ]
+        }
+        None => ["-".to_string(), "-".to_string(), "-".to_string()],
+    };
+    println!(
+        "{} {:>12} {:>12} {:>10} {} created {} modified {} accessed {} {}",
+        get_attrib_string(node.get_attrib()),
+        node.get_size(),
+        valid_size,
+        node.get_start_cluster(),
+        if node.get_is_contiguous() {
+            "contiguous"
+        } else {
+            "fragmented"
+        },
+        timestamps[0],
+        timestamps[1],
+        timestamps[2],
+        node.get_name()
+    );
+}
+
+// Subdirectories are listed after the directory itself as ls -lR does.
+fn list_directory(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    dnid: libexfat::node::Nid,
+    path: &str,
+) -> exfat_utils::Result<()> {
+    let mut sets = std::collections::HashMap::new();
+    let mut first = true;
+    exfat_utils::util::walk_tree(ef, dnid, path, |ef, nid, path, visit| {
+        let node = exfat_utils::util::get_node!(ef, nid);
+        match visit {
+            exfat_utils::util::Visit::Directory => {
+                sets = get_entry_sets(vol, node)?;
+                if !first {
+                    println!();
+                }
+                first = false;
+                println!("{path}:");
+            }
+            exfat_utils::util::Visit::Entry => print_node(node, sets.get(node.get_name())),
+        }
+        Ok(true)
+    })
+}
+
+fn list_file(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    nid: libexfat::node::Nid,
+    path: &str,
+) -> exfat_utils::Result<()> {
+    let parent = path.rsplit_once('/').map_or("", |x| x.0);
+    let dnid = crate::lookup(ef, parent)?;
+    let result = get_entry_sets(vol, exfat_utils::util::get_node!(ef, dnid));
+    exfat_utils::util::get_node_mut!(ef, dnid).put();
+    let sets = result?;
+    let node = exfat_utils::util::get_node!(ef, nid);
+    print_node(node, sets.get(node.get_name()));
+    Ok(())
+}
+
+pub(crate) fn list(spec: &str, path: &str) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let nid = crate::lookup(&mut ef, path)?;
+
+    let result = if exfat_utils::util::get_node!(ef, nid).is_directory() {
+        let path = if path.is_empty() { "/" } else { path };
+        list_directory(&mut ef, &mut vol, nid, path)
+    } else {
+        list_file(&mut ef, &mut vol, nid, path)
+    };
+    exfat_utils::util::get_node_mut!(ef, nid).put();
+    result
+}
@@ -1,3 +1,13 @@
+mod bootcmp;
+mod chain;
+mod dentry;
+mod export;
+mod frag;
+mod image;
+mod list;
+mod owner;
+mod usemap;
+
 fn print_version(prog: &str) {
     exfat_utils::util::print_version(prog);
     println!("Copyright (C) 2011-2023  Andrew Nayenko");
@@ -61,7 +71,66 @@ fn print_other_info(sb: &libexfat::fs::ExfatSuperBlock) {
     println!("Allocated space           {:>9}%", sb.allocated_percent);
 }
 
-fn dump_sb(spec: &str) -> libexfat::Result<()> {
+// raw values, as opposed to the aligned text above
+fn get_super_block_json(sb: &libexfat::fs::ExfatSuperBlock) -> exfat_utils::json::Value {
+    let mut v = exfat_utils::json::Value::new_object();
+    v.insert("volume_serial", u32::from_le(sb.volume_serial));
+    v.insert("version_major", sb.version_major);
+    v.insert("version_minor", sb.version_minor);
+    v.insert("sector_size", sb.get_sector_size());
+    v.insert("cluster_size", sb.get_cluster_size());
+    v.insert("sector_count", u64::from_le(sb.sector_count));
+    v.insert("cluster_count", u32::from_le(sb.cluster_count));
+    v.insert("sector_start", u64::from_le(sb.sector_start));
+    v.insert("fat_sector_start", u32::from_le(sb.fat_sector_start));
+    v.insert("fat_sector_count", u32::from_le(sb.fat_sector_count));
+    v.insert(
+        "cluster_sector_start",
+        u32::from_le(sb.cluster_sector_start),
+    );
+    v.insert("rootdir_cluster", u32::from_le(sb.rootdir_cluster));
+    v.insert("volume_state", u16::from_le(sb.volume_state));
+    v.insert("fat_count", sb.fat_count);
+    v.insert("drive_no", sb.drive_no);
+    v.insert("allocated_percent", sb.allocated_percent);
+    v
+}
+
+fn print_json(spec: &str, mut v: exfat_utils::json::Value) {
+    let exfat_utils::json::Value::Object(x) = &mut v else {
+        unreachable!();
+    };
+    x.insert(0, ("device".to_string(), spec.into()));
+    println!("{}", v.to_pretty_string());
+}
+
+fn mount(spec: &str) -> libexfat::Result<libexfat::exfat::Exfat> {
+    let mut mopt = vec!["--mode", "ro"];
+    if libfs::is_debug_set() {
+        mopt.push("--debug");
+    }
+    libexfat::mount(spec, &mopt)
+}
+
+// libexfat for the tree and the raw reader for entries it doesn't expose,
+// both read-only
+pub(crate) fn open(
+    spec: &str,
+) -> exfat_utils::Result<(libexfat::exfat::Exfat, exfat_utils::volume::Volume)> {
+    let ef = mount(spec)?;
+    let vol = exfat_utils::volume::Volume::open(spec, "ro")?;
+    Ok((ef, vol))
+}
+
+pub(crate) fn lookup(
+    ef: &mut libexfat::exfat::Exfat,
+    path: &str,
+) -> libexfat::Result<libexfat::node::Nid> {
+    ef.lookup(path)
+        .inspect_err(|e| log::error!("'{path}': {e}"))
+}
+
+fn dump_sb(spec: &str, json: bool) -> libexfat::Result<()> {
     let mut dev = libexfat::open(spec, "ro")?;
     let buf = match dev.preadx(libexfat::fs::EXFAT_SUPER_BLOCK_SIZE_U64, 0) {
         Ok(v) => v,
@@ -80,6 +149,12 @@ fn dump_sb(spec: &str) -> libexfat::Result<()> {
         return Err(nix::errno::Errno::EINVAL.into());
     }
 
+    if json {
+        let mut v = exfat_utils::json::Value::new_object();
+        v.insert("super_block", get_super_block_json(&sb));
+        print_json(spec, v);
+        return Ok(());
+    }
     print_generic_info(&sb);
     print_sector_info(&sb);
     print_cluster_info(&sb);
@@ -87,17 +162,34 @@ fn dump_sb(spec: &str) -> libexfat::Result<()> {
     Ok(())
 }
 
-fn dump_full(spec: &str, used_sectors: bool) -> libexfat::Result<()> {
-    let mut mopt = vec!["--mode", "ro"];
-    if libfs::is_debug_set() {
-        mopt.push("--debug");
-    }
-
-    let ef = libexfat::mount(spec, &mopt)?;
+fn dump_full(spec: &str, used_sectors: bool, json: bool) -> libexfat::Result<()> {
+    let ef = mount(spec)?;
     let free_clusters = ef.get_free_clusters()?;
     let sb = ef.get_super_block();
     let free_sectors = free_clusters << sb.spc_bits;
 
+    if json {
+        let mut v = exfat_utils::json::Value::new_object();
+        v.insert("label", ef.get_label());
+        v.insert("super_block", get_super_block_json(&sb));
+        v.insert("free_sectors", free_sectors);
+        v.insert("free_clusters", free_clusters);
+        if used_sectors {
+            let mut a = 0;
+            let mut b = 0;
+            let mut ranges = vec![];
+            while ef.find_used_sectors(&mut a, &mut b)? {
+                let mut x = exfat_utils::json::Value::new_object();
+                x.insert("start", a);
+                x.insert("end", b);
+                ranges.push(x);
+            }
+            v.insert("used_sectors", ranges);
+        }
+        print_json(spec, v);
+        return Ok(());
+    }
+
     println!("Volume label         {:>15}", ef.get_label());
     print_generic_info(&sb);
     print_sector_info(&sb);
@@ -118,26 +210,16 @@ fn dump_full(spec: &str, used_sectors: bool) -> libexfat::Result<()> {
     Ok(())
 }
 
-fn dump_file_fragments(spec: &str, path: &str) -> libexfat::Result<()> {
-    let mut mopt = vec!["--mode", "ro"];
-    if libfs::is_debug_set() {
-        mopt.push("--debug");
-    }
-
-    let mut ef = libexfat::mount(spec, &mopt)?;
-    let nid = match ef.lookup(path) {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("'{path}': {e}");
-            return Err(e);
-        }
-    };
+fn dump_file_fragments(spec: &str, path: &str, json: bool) -> libexfat::Result<()> {
+    let mut ef = mount(spec)?;
+    let nid = lookup(&mut ef, path)?;
 
     let node = exfat_utils::util::get_node!(ef, nid);
     let mut cluster = node.get_start_cluster();
     let mut fragment_start_cluster = cluster;
     let mut remainder = node.get_size();
     let mut fragment_size = 0;
+    let mut fragments = vec![];
 
     while remainder > 0 {
         if ef.cluster_invalid(cluster) {
@@ -152,7 +234,7 @@ fn dump_file_fragments(spec: &str, path: &str) -> libexfat::Result<()> {
         let next_cluster = ef.next_cluster(nid, cluster);
         if next_cluster != cluster + 1 || remainder == 0 {
             // next cluster is not contiguous or this is EOF
-            println!("{} {}", ef.c2o(fragment_start_cluster), fragment_size);
+            fragments.push((ef.c2o(fragment_start_cluster), fragment_size));
             // start a new fragment
             fragment_start_cluster = next_cluster;
             fragment_size = 0;
@@ -161,16 +243,57 @@ fn dump_file_fragments(spec: &str, path: &str) -> libexfat::Result<()> {
     }
 
     exfat_utils::util::get_node_mut!(ef, nid).put();
+
+    if json {
+        let mut v = exfat_utils::json::Value::new_object();
+        v.insert("path", path);
+        v.insert(
+            "fragments",
+            fragments
+                .into_iter()
+                .map(|(offset, size)| {
+                    let mut x = exfat_utils::json::Value::new_object();
+                    x.insert("offset", offset);
+                    x.insert("size", size);
+                    x
+                })
+                .collect::<Vec<_>>(),
+        );
+        print_json(spec, v);
+    } else {
+        for (offset, size) in fragments {
+            println!("{offset} {size}");
+        }
+    }
     Ok(())
 }
 
+// accepts hexadecimal with 0x prefix
+fn get_number_opt(matches: &getopts::Matches, name: &str) -> Option<u64> {
+    let v = matches.opt_str(name)?;
+    let result = match v.strip_prefix("0x") {
+        Some(x) => u64::from_str_radix(x, 16),
+        None => v.parse(),
+    };
+    match result {
+        Ok(v) => Some(v),
+        Err(e) => {
+            log::error!("invalid option value: '{v}': {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
 fn usage(prog: &str, gopt: &getopts::Options) {
     print!(
         "{}",
-        gopt.usage(&format!("Usage: {prog} [-s] [-u] [-f file] [-V] <device>"))
+        gopt.usage(&format!(
+            "Usage: {prog} [-s] [-b] [-u] [-f file] [-C file] [-l [path]] [-e path] [-c cluster] [-o offset] [-F [--top count]] [--bitmap file] [--upcase file] [-r] [--bmap file] [--ddrescue-map file] [-m file [--scramble]] [--json] [-V] <device>"
+        ))
     );
 }
 
+#[allow(clippy::too_many_lines)]
 fn main() {
     if let Err(e) = exfat_utils::util::init_std_logger() {
         eprintln!("{e}");
@@ -186,6 +309,12 @@ fn main() {
         "",
         "Dump only info from super block. May be useful for heavily corrupted file systems.",
     );
+    gopt.optflag(
+        "b",
+        "",
+        "Compare main and backup boot regions. Verify checksums of both and print \
+        super block fields side by side, marking fields which differ.",
+    );
     gopt.optflag(
         "u",
         "",
@@ -200,6 +329,107 @@ fn main() {
         into the file system, and the length (in bytes).",
         "<file>",
     );
+    gopt.optopt(
+        "C",
+        "",
+        "Print the cluster chain of the given file, each cluster with its FAT entry, \
+        flagging clusters not allocated in the bitmap.",
+        "<file>",
+    );
+    gopt.optflagopt(
+        "l",
+        "",
+        "Print a recursive listing of the given directory, or the root directory \
+        by default, with attributes, size, valid data length, start cluster, \
+        whether contiguous or fragmented, and create, modify and access times.",
+        "<path>",
+    );
+    gopt.optopt(
+        "e",
+        "",
+        "Dump every 32 byte slot of the given directory, including deleted and \
+        unused ones, as hex bytes followed by the fields decoded for its entry type.",
+        "<path>",
+    );
+    gopt.optopt(
+        "c",
+        "",
+        "Print the file or system object that owns the given cluster, \
+        or whether the cluster is free.",
+        "<cluster>",
+    );
+    gopt.optopt(
+        "o",
+        "",
+        "Print the file or system object that owns the given byte offset \
+        into the file system, and the offset within it.",
+        "<offset>",
+    );
+    gopt.optflag(
+        "F",
+        "",
+        "Print a fragmentation report of the whole volume, with fragment counts \
+        of files, the most fragmented files, contiguous versus chained files, \
+        and sizes of free extents in the allocation bitmap.",
+    );
+    gopt.optopt(
+        "",
+        "top",
+        "Number of most fragmented files printed by -F, 10 by default. \
+        0 prints the fragment count of every file.",
+        "<count>",
+    );
+    gopt.optopt(
+        "",
+        "bitmap",
+        "Export the raw allocation bitmap to the given file, or stdout if '-'.",
+        "<file>",
+    );
+    gopt.optopt(
+        "",
+        "upcase",
+        "Export the decompressed upcase table to the given file, or stdout if '-'. \
+        Each code point is 2 bytes in little endian.",
+        "<file>",
+    );
+    gopt.optflag(
+        "r",
+        "",
+        "Export --bitmap as allocated and free runs of clusters, and --upcase as \
+        the checksum and code points not mapped to themselves, instead of raw bytes.",
+    );
+    gopt.optopt(
+        "",
+        "bmap",
+        "Write used sectors as a bmaptool XML bmap with SHA-256 checksums of ranges \
+        to the given file, or stdout if '-'.",
+        "<file>",
+    );
+    gopt.optopt(
+        "",
+        "ddrescue-map",
+        "Write used sectors as a ddrescue mapfile, used as finished and unused as \
+        non-tried, to the given file, or stdout if '-'.",
+        "<file>",
+    );
+    gopt.optopt(
+        "m",
+        "",
+        "Write a sparse image of metadata only, boot regions, FAT, allocation bitmap, \
+        upcase table and directories, to the given file. File data is left as holes.",
+        "<file>",
+    );
+    gopt.optflag(
+        "",
+        "scramble",
+        "Replace file names in the -m image, keeping name hashes and checksums \
+        consistent. Names of deleted entries are cleared.",
+    );
+    gopt.optflag(
+        "",
+        "json",
+        "Print output of -s, -u, -f or the default mode as JSON with raw numbers.",
+    );
     gopt.optflag("V", "version", "Print version and copyright.");
     gopt.optflag("h", "help", "Print usage.");
 
@@ -221,28 +451,123 @@ fn main() {
     }
 
     let sb_only = matches.opt_present("s");
+    let boot_regions = matches.opt_present("b");
     let used_sectors = matches.opt_present("u");
     let file_path = matches.opt_str("f");
+    let chain_path = matches.opt_str("C");
+    let entries_path = matches.opt_str("e");
+    let cluster = get_number_opt(&matches, "c");
+    let offset = get_number_opt(&matches, "o");
+    let fragmentation = matches.opt_present("F");
+    let json = matches.opt_present("json");
+    let bitmap_file = matches.opt_str("bitmap");
+    let upcase_file = matches.opt_str("upcase");
+    let readable = matches.opt_present("r");
+    let bmap_file = matches.opt_str("bmap");
+    let image_file = matches.opt_str("m");
+    let scramble = matches.opt_present("scramble");
+    let ddrescue_map_file = matches.opt_str("ddrescue-map");
+    let top = get_number_opt(&matches, "top").unwrap_or(10);
+    let mut list_path = if matches.opt_present("l") {
+        Some(matches.opt_str("l").unwrap_or("/".to_string()))
+    } else {
+        None
+    };
 
-    let args = matches.free;
+    let mut args = matches.free;
+    // -l takes the device as its path if the path is omitted
+    if args.is_empty() {
+        if let Some(v) = &list_path {
+            args.push(v.clone());
+            list_path = Some("/".to_string());
+        }
+    }
     if args.len() != 1 {
         usage(prog, &gopt);
         std::process::exit(1);
     }
     let spec = &args[0];
 
-    if let Some(file_path) = file_path {
-        if let Err(e) = dump_file_fragments(spec, &file_path) {
+    if let Some(list_path) = list_path {
+        if let Err(e) = list::list(spec, &list_path) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(f) = image_file {
+        if let Err(e) = image::write_image(spec, &f, scramble) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(f) = bmap_file {
+        if let Err(e) = usemap::write_map(spec, &f, true) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(f) = ddrescue_map_file {
+        if let Err(e) = usemap::write_map(spec, &f, false) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(f) = bitmap_file {
+        if let Err(e) = export::export_bitmap(spec, &f, readable) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(f) = upcase_file {
+        if let Err(e) = export::export_upcase(spec, &f, readable) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if fragmentation {
+        let result = match usize::try_from(top) {
+            Ok(v) => frag::report(spec, v),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(cluster) = cluster {
+        let result = match u32::try_from(cluster) {
+            Ok(v) => owner::lookup_cluster(spec, v),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(offset) = offset {
+        if let Err(e) = owner::lookup_offset(spec, offset) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(entries_path) = entries_path {
+        if let Err(e) = dentry::dump_entries(spec, &entries_path) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(chain_path) = chain_path {
+        if let Err(e) = chain::print_chain(spec, &chain_path) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if let Some(file_path) = file_path {
+        if let Err(e) = dump_file_fragments(spec, &file_path, json) {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    } else if boot_regions {
+        if let Err(e) = bootcmp::compare_boot_regions(spec) {
             log::error!("{e}");
             std::process::exit(1);
         }
     } else if sb_only {
-        if let Err(e) = dump_sb(spec) {
+        if let Err(e) = dump_sb(spec, json) {
             log::error!("{e}");
             std::process::exit(1);
         }
     } else if true {
-        if let Err(e) = dump_full(spec, used_sectors) {
+        if let Err(e) = dump_full(spec, used_sectors, json) {
             log::error!("{e}");
             std::process::exit(1);
         }
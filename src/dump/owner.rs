@@ -0,0 +1,162 @@
+// object name and the offset within it
+type Owner = (String, u64);
+
+fn find_in_chain(
+    vol: &exfat_utils::volume::Volume,
+    chain: &[u32],
+    cluster: u32,
+    name: &str,
+) -> Option<Owner> {
+    chain
+        .iter()
+        .position(|x| *x == cluster)
+        .map(|i| (name.to_string(), vol.get_cluster_size() * i as u64))
+}
+
+// The root directory and the special objects it points to.
+fn find_system_owner(
+    vol: &mut exfat_utils::volume::Volume,
+    cluster: u32,
+) -> exfat_utils::Result<Option<Owner>> {
+    let rootdir_cluster = vol.get_rootdir_cluster();
+    let chain = vol.get_cluster_chain(rootdir_cluster, false, None)?;
+    if let Some(v) = find_in_chain(vol, &chain, cluster, "root directory") {
+        return Ok(Some(v));
+    }
+    for (typ, name, start_cluster, size) in [
+        (
+            libexfat::fs::EXFAT_ENTRY_BITMAP,
+            "allocation bitmap",
+            exfat_utils::entry::BITMAP_START_CLUSTER,
+            exfat_utils::entry::BITMAP_SIZE,
+        ),
+        (
+            libexfat::fs::EXFAT_ENTRY_UPCASE,
+            "upcase table",
+            exfat_utils::entry::UPCASE_START_CLUSTER,
+            exfat_utils::entry::UPCASE_SIZE,
+        ),
+    ] {
+        let Some(slot) = vol.find_root_entry(typ)? else {
+            continue;
+        };
+        let chain =
+            vol.get_cluster_chain(slot.get_u32(start_cluster), false, Some(slot.get_u64(size)))?;
+        if let Some(v) = find_in_chain(vol, &chain, cluster, name) {
+            return Ok(Some(v));
+        }
+    }
+    Ok(None)
+}
+
+fn find_file_owner(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    dnid: libexfat::node::Nid,
+    cluster: u32,
+) -> exfat_utils::Result<Option<Owner>> {
+    let mut owner = None;
+    exfat_utils::util::walk_tree(ef, dnid, "/", |ef, nid, path, visit| {
+        if visit == exfat_utils::util::Visit::Directory {
+            return Ok(true);
+        }
+        let node = exfat_utils::util::get_node!(ef, nid);
+        // a broken file elsewhere must not keep the owner from being found
+        match vol.get_cluster_chain(
+            node.get_start_cluster(),
+            node.get_is_contiguous(),
+            Some(node.get_size()),
+        ) {
+            Ok(v) => owner = find_in_chain(vol, &v, cluster, path),
+            Err(e) => log::warn!("skipping '{path}' with broken cluster chain: {e}"),
+        }
+        Ok(owner.is_none())
+    })?;
+    Ok(owner)
+}
+
+// Describes the owner of the cluster, the offset is the one within it.
+fn get_cluster_owner(
+    ef: &mut libexfat::exfat::Exfat,
+    vol: &mut exfat_utils::volume::Volume,
+    cluster: u32,
+    offset: u64,
+) -> exfat_utils::Result<String> {
+    let mut owner = find_system_owner(vol, cluster)?;
+    if owner.is_none() {
+        let nid = crate::lookup(ef, "/")?;
+        let result = find_file_owner(ef, vol, nid, cluster);
+        exfat_utils::util::get_node_mut!(ef, nid).put();
+        owner = result?;
+    }
+
+    Ok(if let Some((name, x)) = owner {
+        if name.starts_with('/') {
+            format!("belongs to file '{name}' at offset {}", x + offset)
+        } else {
+            format!("belongs to the {name} at offset {}", x + offset)
+        }
+    } else if vol.get_fat_entry(cluster)? == libexfat::fs::EXFAT_CLUSTER_BAD {
+        "is marked bad".to_string()
+    } else if ef
+        .is_cluster_allocated((cluster - libexfat::fs::EXFAT_FIRST_DATA_CLUSTER).try_into()?)?
+    {
+        "is allocated but not owned by any file".to_string()
+    } else {
+        "is free".to_string()
+    })
+}
+
+pub(crate) fn lookup_cluster(spec: &str, cluster: u32) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    if vol.cluster_invalid(cluster) {
+        log::error!("cluster {cluster:#x} is outside of the cluster heap");
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+    println!(
+        "cluster {cluster:#x} {}",
+        get_cluster_owner(&mut ef, &mut vol, cluster, 0)?
+    );
+    Ok(())
+}
+
+pub(crate) fn lookup_offset(spec: &str, offset: u64) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let sb = *vol.get_super_block();
+    let sector_size = vol.get_sector_size();
+    let sector = offset / sector_size;
+    let fat_start = u64::from(u32::from_le(sb.fat_sector_start)) * sector_size;
+    let fat_size = u64::from(u32::from_le(sb.fat_sector_count)) * sector_size;
+    let heap_start = u64::from(u32::from_le(sb.cluster_sector_start)) * sector_size;
+
+    if sector < 12 {
+        println!("offset {offset} belongs to the main boot region at sector {sector}");
+    } else if sector < 24 {
+        println!(
+            "offset {offset} belongs to the backup boot region at sector {}",
+            sector - 12
+        );
+    } else if offset >= fat_start && offset < fat_start + fat_size {
+        let x = offset - fat_start;
+        println!(
+            "offset {offset} belongs to the FAT at offset {x}, entry of cluster {:#x}",
+            x / 4
+        );
+    } else if offset >= heap_start {
+        let x = offset - heap_start;
+        let cluster_size = vol.get_cluster_size();
+        let cluster =
+            u32::try_from(x / cluster_size + u64::from(libexfat::fs::EXFAT_FIRST_DATA_CLUSTER))?;
+        if vol.cluster_invalid(cluster) {
+            println!("offset {offset} is beyond the cluster heap");
+            return Ok(());
+        }
+        println!(
+            "offset {offset} in cluster {cluster:#x} {}",
+            get_cluster_owner(&mut ef, &mut vol, cluster, x % cluster_size)?
+        );
+    } else {
+        println!("offset {offset} is not used by any structure");
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::fmt::Write;
+
+const READ_SIZE_MAX: u64 = 1 << 20;
+
+// Inclusive ranges of sectors in use.
+fn get_used_ranges(ef: &mut libexfat::exfat::Exfat) -> libexfat::Result<Vec<(u64, u64)>> {
+    let mut v = vec![];
+    let mut a = 0;
+    let mut b = 0;
+    while ef.find_used_sectors(&mut a, &mut b)? {
+        v.push((a, b));
+    }
+    Ok(v)
+}
+
+fn get_range_checksum(
+    vol: &mut exfat_utils::volume::Volume,
+    offset: u64,
+    size: u64,
+) -> exfat_utils::Result<String> {
+    let mut h = exfat_utils::sha256::Sha256::new();
+    let mut x = 0;
+    while x < size {
+        let n = std::cmp::min(READ_SIZE_MAX, size - x);
+        h.update(&vol.pread(n, offset + x)?);
+        x += n;
+    }
+    Ok(exfat_utils::sha256::to_hex_string(&h.finalize()))
+}
+
+// The file checksum is calculated with the field filled with zeros and
+// then put in its place, as bmaptool verifies it.
+fn get_bmap(
+    vol: &mut exfat_utils::volume::Volume,
+    ranges: &[(u64, u64)],
+) -> exfat_utils::Result<String> {
+    let block_size = vol.get_sector_size();
+    let blocks = u64::from_le(vol.get_super_block().sector_count);
+    let mapped: u64 = ranges.iter().map(|(a, b)| b - a + 1).sum();
+    let zeros = "0".repeat(64);
+
+    let mut s = String::new();
+    writeln!(s, "<?xml version=\"1.0\" ?>")?;
+    writeln!(s, "<bmap version=\"2.0\">")?;
+    writeln!(s, "    <ImageSize> {} </ImageSize>", blocks * block_size)?;
+    writeln!(s, "    <BlockSize> {block_size} </BlockSize>")?;
+    writeln!(s, "    <BlocksCount> {blocks} </BlocksCount>")?;
+    writeln!(s, "    <MappedBlocksCount> {mapped} </MappedBlocksCount>")?;
+    writeln!(s, "    <ChecksumType> sha256 </ChecksumType>")?;
+    writeln!(s, "    <BmapFileChecksum> {zeros} </BmapFileChecksum>")?;
+    writeln!(s, "    <BlockMap>")?;
+    for (a, b) in ranges {
+        let checksum = get_range_checksum(vol, a * block_size, (b - a + 1) * block_size)?;
+        if a == b {
+            writeln!(s, "        <Range chksum=\"{checksum}\"> {a} </Range>")?;
+        } else {
+            writeln!(s, "        <Range chksum=\"{checksum}\"> {a}-{b} </Range>")?;
+        }
+    }
+    writeln!(s, "    </BlockMap>")?;
+    writeln!(s, "</bmap>")?;
+
+    let mut h = exfat_utils::sha256::Sha256::new();
+    h.update(s.as_bytes());
+    let checksum = exfat_utils::sha256::to_hex_string(&h.finalize());
+    Ok(s.replacen(&zeros, &checksum, 1))
+}
+
+// Used sectors are finished ('+') and the rest non-tried ('?'), so that
+// the mapfile works as a domain mapfile for ddrescue -m.
+fn get_ddrescue_map(
+    vol: &exfat_utils::volume::Volume,
+    ranges: &[(u64, u64)],
+) -> exfat_utils::Result<String> {
+    let sector_size = vol.get_sector_size();
+    let size = u64::from_le(vol.get_super_block().sector_count) * sector_size;
+
+    let mut s = String::new();
+    writeln!(s, "# Mapfile. Created by dumpexfat")?;
+    writeln!(s, "# current_pos  current_status  current_pass")?;
+    writeln!(s, "0x00000000     +               1")?;
+    writeln!(s, "#      pos        size  status")?;
+    let mut pos = 0;
+    for (a, b) in ranges {
+        let start = a * sector_size;
+        let end = (b + 1) * sector_size;
+        if start > pos {
+            writeln!(s, "{pos:#010x}  {:#010x}  ?", start - pos)?;
+        }
+        writeln!(s, "{start:#010x}  {:#010x}  +", end - start)?;
+        pos = end;
+    }
+    if size > pos {
+        writeln!(s, "{pos:#010x}  {:#010x}  ?", size - pos)?;
+    }
+    Ok(s)
+}
+
+pub(crate) fn write_map(spec: &str, f: &str, bmap: bool) -> exfat_utils::Result<()> {
+    let (mut ef, mut vol) = crate::open(spec)?;
+    let ranges = get_used_ranges(&mut ef)?;
+    let s = if bmap {
+        get_bmap(&mut vol, &ranges)?
+    } else {
+        get_ddrescue_map(&vol, &ranges)?
+    };
+    if f == "-" {
+        print!("{s}");
+    } else {
+        std::fs::write(f, s)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use byteorder::ByteOrder;
+
+// end of directory marker, every following slot is unused as well
+pub const EXFAT_ENTRY_EOD: u8 = 0x00;
+// volume GUID, a benign primary entry
+pub const EXFAT_ENTRY_GUID: u8 =
+    libexfat::fs::EXFAT_ENTRY_VALID | libexfat::fs::EXFAT_ENTRY_OPTIONAL;
+// vendor extension and vendor allocation, benign secondary entries
+pub const EXFAT_ENTRY_VENDOR_EXT: u8 = libexfat::fs::EXFAT_ENTRY_VALID
+    | libexfat::fs::EXFAT_ENTRY_CONTINUED
+    | libexfat::fs::EXFAT_ENTRY_OPTIONAL;
+pub const EXFAT_ENTRY_VENDOR_ALLOC: u8 = EXFAT_ENTRY_VENDOR_EXT | 0x01;
+
+/// A 32-byte directory entry slot and where it lives on the device.
+#[derive(Clone, Copy, Debug)]
+pub struct Slot {
+    cluster: u32,
+    offset: u64,
+    buf: [u8; libexfat::fs::EXFAT_ENTRY_SIZE],
+}
+
+impl Slot {
+    #[must_use]
+    pub fn new(cluster: u32, offset: u64, buf: [u8; libexfat::fs::EXFAT_ENTRY_SIZE]) -> Self {
+        Self {
+            cluster,
+            offset,
+            buf,
+        }
+    }
+
+    #[must_use]
+    pub fn get_cluster(&self) -> u32 {
+        self.cluster
+    }
+
+    #[must_use]
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; libexfat::fs::EXFAT_ENTRY_SIZE] {
+        &self.buf
+    }
+
+    #[must_use]
+    pub fn get_type(&self) -> u8 {
+        self.buf[0]
+    }
+
+    #[must_use]
+    pub fn is_eod(&self) -> bool {
+        self.get_type() == EXFAT_ENTRY_EOD
+    }
+
+    #[must_use]
+    pub fn get_u8(&self, i: usize) -> u8 {
+        self.buf[i]
+    }
+
+    #[must_use]
+    pub fn get_u16(&self, i: usize) -> u16 {
+        byteorder::LittleEndian::read_u16(&self.buf[i..i + 2])
+    }
+
+    #[must_use]
+    pub fn get_u32(&self, i: usize) -> u32 {
+        byteorder::LittleEndian::read_u32(&self.buf[i..i + 4])
+    }
+
+    #[must_use]
+    pub fn get_u64(&self, i: usize) -> u64 {
+        byteorder::LittleEndian::read_u64(&self.buf[i..i + 8])
+    }
+
+    pub fn set_u8(&mut self, i: usize, v: u8) {
+        self.buf[i] = v;
+    }
+
+    pub fn set_u16(&mut self, i: usize, v: u16) {
+        byteorder::LittleEndian::write_u16(&mut self.buf[i..i + 2], v);
+    }
+
+    pub fn set_u32(&mut self, i: usize, v: u32) {
+        byteorder::LittleEndian::write_u32(&mut self.buf[i..i + 4], v);
+    }
+
+    pub fn set_u64(&mut self, i: usize, v: u64) {
+        byteorder::LittleEndian::write_u64(&mut self.buf[i..i + 8], v);
+    }
+}
+
+// allocation bitmap entry
+pub const BITMAP_FLAGS: usize = 1;
+pub const BITMAP_START_CLUSTER: usize = 20;
+pub const BITMAP_SIZE: usize = 24;
+
+// upcase table entry
+pub const UPCASE_CHECKSUM: usize = 4;
+pub const UPCASE_START_CLUSTER: usize = 20;
+pub const UPCASE_SIZE: usize = 24;
+
+// volume label entry
+pub const LABEL_LENGTH: usize = 1;
+pub const LABEL_CHARS: usize = 2;
+pub const LABEL_CHARS_MAX: usize = 11;
+
+// volume GUID entry
+pub const GUID_SECONDARY_COUNT: usize = 1;
+pub const GUID_CHECKSUM: usize = 2;
+pub const GUID_FLAGS: usize = 4;
+pub const GUID_GUID: usize = 6;
+
+// file entry
+pub const FILE_SECONDARY_COUNT: usize = 1;
+pub const FILE_CHECKSUM: usize = 2;
+pub const FILE_ATTRIB: usize = 4;
+pub const FILE_CRTIME: usize = 8;
+pub const FILE_MTIME: usize = 12;
+pub const FILE_ATIME: usize = 16;
+pub const FILE_CRTIME_CS: usize = 20;
+pub const FILE_MTIME_CS: usize = 21;
+pub const FILE_CRTIME_TZO: usize = 22;
+pub const FILE_MTIME_TZO: usize = 23;
+pub const FILE_ATIME_TZO: usize = 24;
+
+// stream extension entry
+pub const STREAM_FLAGS: usize = 1;
+pub const STREAM_NAME_LENGTH: usize = 3;
+pub const STREAM_NAME_HASH: usize = 4;
+pub const STREAM_VALID_SIZE: usize = 8;
+pub const STREAM_START_CLUSTER: usize = 20;
+pub const STREAM_SIZE: usize = 24;
+
+// file name entry
+pub const NAME_CHARS: usize = 2;
+pub const NAME_CHARS_PER_ENTRY: usize = 15;
+
+// vendor extension and vendor allocation entries
+pub const VENDOR_FLAGS: usize = 1;
+pub const VENDOR_GUID: usize = 2;
+pub const VENDOR_START_CLUSTER: usize = 20;
+pub const VENDOR_SIZE: usize = 24;
+
+#[must_use]
+pub fn is_secondary(typ: u8) -> bool {
+    // in use and of secondary type category
+    (typ & (libexfat::fs::EXFAT_ENTRY_VALID | libexfat::fs::EXFAT_ENTRY_CONTINUED))
+        == (libexfat::fs::EXFAT_ENTRY_VALID | libexfat::fs::EXFAT_ENTRY_CONTINUED)
+}
+
+/// A file entry followed by its secondary entries.
+#[derive(Clone, Debug)]
+pub struct EntrySet {
+    slots: Vec<Slot>,
+}
+
+impl EntrySet {
+    #[must_use]
+    pub fn get_slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    #[must_use]
+    pub fn get_file(&self) -> &Slot {
+        &self.slots[0]
+    }
+
+    pub fn get_file_mut(&mut self) -> &mut Slot {
+        &mut self.slots[0]
+    }
+
+    #[must_use]
+    pub fn get_secondary_count(&self) -> usize {
+        self.get_file().get_u8(FILE_SECONDARY_COUNT).into()
+    }
+
+    #[must_use]
+    pub fn get_stream(&self) -> Option<&Slot> {
+        self.slots
+            .get(1)
+            .filter(|x| x.get_type() == libexfat::fs::EXFAT_ENTRY_FILE_INFO)
+    }
+
+    pub fn get_stream_mut(&mut self) -> Option<&mut Slot> {
+        self.slots
+            .get_mut(1)
+            .filter(|x| x.get_type() == libexfat::fs::EXFAT_ENTRY_FILE_INFO)
+    }
+
+    #[must_use]
+    pub fn get_name_entries_count(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|x| x.get_type() == libexfat::fs::EXFAT_ENTRY_FILE_NAME)
+            .count()
+    }
+
+    /// All characters of file name entries, including trailing zeros.
+    #[must_use]
+    pub fn get_name_chars(&self) -> Vec<u16> {
+        let mut name = vec![];
+        for x in &self.slots {
+            if x.get_type() == libexfat::fs::EXFAT_ENTRY_FILE_NAME {
+                for i in 0..NAME_CHARS_PER_ENTRY {
+                    name.push(x.get_u16(NAME_CHARS + i * 2));
+                }
+            }
+        }
+        name
+    }
+
+    /// Name as stored in file name entries, limited by the name length
+    /// of the stream extension if any.
+    #[must_use]
+    pub fn get_name(&self) -> Vec<u16> {
+        let mut name = self.get_name_chars();
+        match self.get_stream() {
+            Some(v) => name.truncate(v.get_u8(STREAM_NAME_LENGTH).into()),
+            None => name.truncate(libexfat::utf::utf16_length(&name)),
+        }
+        name
+    }
+
+    /// Caller makes sure the name fits in existing file name entries.
+    ///
+    /// # Panics
+    pub fn set_name(&mut self, name: &[u16]) {
+        assert!(name.len() <= self.get_name_entries_count() * NAME_CHARS_PER_ENTRY);
+        assert!(name.len() <= libexfat::fs::EXFAT_NAME_MAX);
+        let mut i = 0;
+        for x in &mut self.slots {
+            if x.get_type() == libexfat::fs::EXFAT_ENTRY_FILE_NAME {
+                for j in 0..NAME_CHARS_PER_ENTRY {
+                    x.set_u16(NAME_CHARS + j * 2, name.get(i).copied().unwrap_or(0));
+                    i += 1;
+                }
+            }
+        }
+        if let Some(v) = self.get_stream_mut() {
+            v.set_u8(STREAM_NAME_LENGTH, u8::try_from(name.len()).unwrap());
+        }
+    }
+
+    #[must_use]
+    pub fn calc_checksum(&self) -> u16 {
+        calc_set_checksum(&self.slots)
+    }
+
+    pub fn update_checksum(&mut self) {
+        let sum = self.calc_checksum();
+        self.get_file_mut().set_u16(FILE_CHECKSUM, sum);
+    }
+}
+
+/// Format a file entry timestamp as YYYY-MM-DD hh:mm:ss.cc, followed by
+/// the UTC offset if valid. Fields are printed as stored, even if invalid.
+#[must_use]
+pub fn format_timestamp(time: u32, cs: u8, tzo: u8) -> String {
+    let zone = if tzo & 0x80 == 0 {
+        String::new()
+    } else {
+        // signed 7 bit offset in 15 minute increments
+        let offset = i32::from(i8::from_ne_bytes([tzo << 1]) >> 1) * 15;
+        format!(
+            " {}{:02}:{:02}",
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60
+        )
+    };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:02}{zone}",
+        (time >> 25) + 1980,
+        (time >> 21) & 0xf,
+        (time >> 16) & 0x1f,
+        (time >> 11) & 0x1f,
+        (time >> 5) & 0x3f,
+        (time & 0x1f) * 2 + u32::from(cs / 100),
+        cs % 100
+    )
+}
+
+/// Checksum of a primary entry and its secondary entries.
+#[must_use]
+pub fn calc_set_checksum(slots: &[Slot]) -> u16 {
+    let mut sum = 0u16;
+    for (i, x) in slots.iter().enumerate() {
+        for (j, b) in x.as_bytes().iter().enumerate() {
+            // skip the checksum field itself, at the same offset for
+            // every primary entry type with a set checksum
+            if i == 0 && (j == FILE_CHECKSUM || j == FILE_CHECKSUM + 1) {
+                continue;
+            }
+            sum = sum.rotate_right(1).wrapping_add(u16::from(*b));
+        }
+    }
+    sum
+}
+
+#[must_use]
+pub fn calc_name_hash(upcase: &[u16], name: &[u16]) -> u16 {
+    let mut hash = 0u16;
+    for c in name {
+        let c = upcase[usize::from(*c)];
+        for b in c.to_le_bytes() {
+            hash = hash.rotate_right(1).wrapping_add(u16::from(b));
+        }
+    }
+    hash
+}
+
+/// Collect entry sets up to the end of directory marker. Secondary entries
+/// are taken as long as they are in use, even if fewer than the secondary
+/// count of the file entry.
+#[must_use]
+pub fn get_entry_sets(slots: &[Slot]) -> Vec<EntrySet> {
+    let mut v = vec![];
+    let mut i = 0;
+    while i < slots.len() {
+        let slot = &slots[i];
+        if slot.is_eod() {
+            break;
+        }
+        i += 1;
+        if slot.get_type() != libexfat::fs::EXFAT_ENTRY_FILE {
+            continue;
+        }
+        let mut es = EntrySet { slots: vec![*slot] };
+        while i < slots.len()
+            && es.slots.len() <= es.get_secondary_count()
+            && is_secondary(slots[i].get_type())
+        {
+            es.slots.push(slots[i]);
+            i += 1;
+        }
+        v.push(es);
+    }
+    v
+}
@@ -0,0 +1,44 @@
+pub(crate) fn write_entry_set(
+    vol: &mut exfat_utils::volume::Volume,
+    es: &exfat_utils::entry::EntrySet,
+) -> exfat_utils::Result<()> {
+    for slot in es.get_slots() {
+        vol.write_slot(slot)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn get_name_string(name: &[u16]) -> String {
+    String::from_utf16_lossy(name)
+}
+
+// first data cluster of the file, none if it's empty
+pub(crate) fn get_start_cluster(es: &exfat_utils::entry::EntrySet) -> Option<u32> {
+    es.get_stream()
+        .map(|x| x.get_u32(exfat_utils::entry::STREAM_START_CLUSTER))
+        .filter(|x| *x != 0)
+}
+
+// Check raw directory entries libexfat either doesn't look at or
+// silently drops. This runs before libexfat reads the directory,
+// so that libexfat loads nodes from repaired entries. The root
+// directory is the exception, libexfat::mount has already read it,
+// so its nodes keep the names and attributes found before repair.
+pub(crate) fn dentryck(
+    state: &mut crate::FsckState,
+    path: &str,
+    start_cluster: u32,
+    is_contiguous: bool,
+    size: u64,
+) -> exfat_utils::Result<()> {
+    let slots = state
+        .vol
+        .read_directory(start_cluster, is_contiguous, Some(size))?;
+    let mut sets = exfat_utils::entry::get_entry_sets(&slots);
+    crate::entryset::entrysetck(state, path, &mut sets)?;
+    crate::name::nameck(state, path, &mut sets)?;
+    // after names are fixed, sanitized names may collide
+    crate::duplicate::duplicateck(state, path, &mut sets)?;
+    crate::time::timeck(state, path, &mut sets)?;
+    Ok(())
+}
@@ -0,0 +1,103 @@
+const SUFFIX_MAX: usize = 9999;
+
+fn get_upcase_name(upcase: &[u16], name: &[u16]) -> Vec<u16> {
+    name.iter().map(|c| upcase[usize::from(*c)]).collect()
+}
+
+// Append ~N to the base name, preserving the extension if any. The name
+// is shortened if needed, so that it fits in existing file name entries.
+fn get_unique_name(
+    upcase: &[u16],
+    names: &std::collections::HashSet<Vec<u16>>,
+    name: &[u16],
+    capacity: usize,
+) -> Option<Vec<u16>> {
+    let dot = u16::from(b'.');
+    let (base, ext) = match name.iter().rposition(|c| *c == dot) {
+        Some(i) if i > 0 => (&name[..i], &name[i..]),
+        _ => (name, &name[name.len()..]),
+    };
+    for i in 1..=SUFFIX_MAX {
+        let suffix: Vec<u16> = format!("~{i}").encode_utf16().collect();
+        let ext = if suffix.len() + ext.len() < capacity {
+            ext
+        } else {
+            &ext[..0]
+        };
+        let n = std::cmp::min(base.len(), capacity - suffix.len() - ext.len());
+        let mut x = base[..n].to_vec();
+        x.extend_from_slice(&suffix);
+        x.extend_from_slice(ext);
+        if !names.contains(&get_upcase_name(upcase, &x)) {
+            return Some(x);
+        }
+    }
+    None
+}
+
+fn rename(
+    state: &mut crate::FsckState,
+    names: &std::collections::HashSet<Vec<u16>>,
+    es: &mut exfat_utils::entry::EntrySet,
+) -> exfat_utils::Result<Option<Vec<u16>>> {
+    if es.get_stream().is_none() || es.get_name_entries_count() == 0 {
+        return Ok(None);
+    }
+    let capacity = std::cmp::min(
+        es.get_name_entries_count() * exfat_utils::entry::NAME_CHARS_PER_ENTRY,
+        libexfat::fs::EXFAT_NAME_MAX,
+    );
+    let Some(name) = get_unique_name(&state.upcase, names, &es.get_name(), capacity) else {
+        return Ok(None);
+    };
+    es.set_name(&name);
+    let hash = exfat_utils::entry::calc_name_hash(&state.upcase, &name);
+    if let Some(v) = es.get_stream_mut() {
+        v.set_u16(exfat_utils::entry::STREAM_NAME_HASH, hash);
+    }
+    es.update_checksum();
+    crate::dentry::write_entry_set(&mut state.vol, es)?;
+    Ok(Some(name))
+}
+
+// exFAT names are case insensitive as defined by the upcase table of
+// the volume, libexfat lookup finds only one of colliding entries.
+pub(crate) fn duplicateck(
+    state: &mut crate::FsckState,
+    path: &str,
+    sets: &mut [exfat_utils::entry::EntrySet],
+) -> exfat_utils::Result<()> {
+    let mut names = std::collections::HashSet::new();
+    for es in sets {
+        let name = es.get_name();
+        if names.insert(get_upcase_name(&state.upcase, &name)) {
+            continue;
+        }
+        let s = crate::dentry::get_name_string(&name);
+        if !state.ask_to_fix(crate::report::Problem::new(
+            crate::report::ProblemType::DuplicateName,
+            Some(&exfat_utils::util::get_entry_path(path, &s)),
+            crate::dentry::get_start_cluster(es),
+            &format!("duplicate name '{s}' in '{path}', rename"),
+        ))? {
+            continue;
+        }
+        if let Some(v) = rename(state, &names, es)? {
+            // libexfat read the root directory on mount
+            log::info!(
+                "renamed '{s}' to '{}' in '{path}'{}",
+                crate::dentry::get_name_string(&v),
+                if path == "/" {
+                    ", later messages show the old name"
+                } else {
+                    ""
+                }
+            );
+            names.insert(get_upcase_name(&state.upcase, &v));
+            state.fixed();
+        } else {
+            log::error!("failed to find a new name for '{s}' in '{path}'");
+        }
+    }
+    Ok(())
+}
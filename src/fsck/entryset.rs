@@ -0,0 +1,115 @@
+// Number of characters stored in file name entries, used as the name
+// length if the stream extension doesn't agree with them.
+fn get_stored_name_length(es: &exfat_utils::entry::EntrySet) -> usize {
+    std::cmp::min(
+        libexfat::utf::utf16_length(&es.get_name_chars()),
+        libexfat::fs::EXFAT_NAME_MAX,
+    )
+}
+
+fn fix_entry_set(
+    state: &mut crate::FsckState,
+    es: &mut exfat_utils::entry::EntrySet,
+    name_length: bool,
+) -> exfat_utils::Result<()> {
+    let count = u8::try_from(es.get_slots().len() - 1)?;
+    es.get_file_mut()
+        .set_u8(exfat_utils::entry::FILE_SECONDARY_COUNT, count);
+    if name_length {
+        let mut name = es.get_name_chars();
+        name.truncate(get_stored_name_length(es));
+        es.set_name(&name);
+        let hash = exfat_utils::entry::calc_name_hash(&state.upcase, &name);
+        if let Some(v) = es.get_stream_mut() {
+            v.set_u16(exfat_utils::entry::STREAM_NAME_HASH, hash);
+        }
+    }
+    es.update_checksum();
+    crate::dentry::write_entry_set(&mut state.vol, es)
+}
+
+// Messages give the location of the file entry, so that the entry set
+// can be found with a hex editor.
+pub(crate) fn entrysetck(
+    state: &mut crate::FsckState,
+    path: &str,
+    sets: &mut [exfat_utils::entry::EntrySet],
+) -> exfat_utils::Result<()> {
+    for es in sets {
+        let file = *es.get_file();
+        let mut problems = vec![];
+
+        let Some(stream) = es.get_stream() else {
+            // nothing to recompute from, libexfat rejects the entry set
+            let s = crate::dentry::get_name_string(&es.get_name());
+            state.error(crate::report::Problem::new(
+                crate::report::ProblemType::EntrySetStream,
+                Some(&exfat_utils::util::get_entry_path(path, &s)),
+                None,
+                &format!(
+                    "entry set '{s}' in '{path}' at cluster {:#x} offset {:#x} has no stream extension",
+                    file.get_cluster(),
+                    file.get_offset()
+                ),
+            ));
+            continue;
+        };
+        let length = usize::from(stream.get_u8(exfat_utils::entry::STREAM_NAME_LENGTH));
+
+        let count = es.get_secondary_count();
+        let actual = es.get_slots().len() - 1;
+        if count != actual {
+            problems.push((
+                crate::report::ProblemType::EntrySetSecondaryCount,
+                format!("secondary count {count} but {actual} secondary entries"),
+            ));
+        }
+        let entries = es.get_name_entries_count();
+        let expected = libexfat::div_round_up!(length, exfat_utils::entry::NAME_CHARS_PER_ENTRY);
+        // an empty name is left to nameck
+        let name_length = entries != expected && get_stored_name_length(es) != 0;
+        if name_length {
+            problems.push((
+                crate::report::ProblemType::NameLength,
+                format!("name length {length} but {entries} name entries"),
+            ));
+        }
+        let checksum = file.get_u16(exfat_utils::entry::FILE_CHECKSUM);
+        let actual = es.calc_checksum();
+        if checksum != actual {
+            problems.push((
+                crate::report::ProblemType::EntrySetChecksum,
+                format!("checksum {checksum:#06x} but {actual:#06x} calculated"),
+            ));
+        }
+        let Some((typ, _)) = problems.first() else {
+            continue;
+        };
+        let checksum_only =
+            problems.len() == 1 && *typ == crate::report::ProblemType::EntrySetChecksum;
+
+        let s = crate::dentry::get_name_string(&es.get_name());
+        let msg: Vec<&str> = problems.iter().map(|x| x.1.as_str()).collect();
+        let fix = state.ask_to_fix(crate::report::Problem::new(
+            *typ,
+            Some(&exfat_utils::util::get_entry_path(path, &s)),
+            crate::dentry::get_start_cluster(es),
+            &format!(
+                "entry set '{s}' in '{path}' at cluster {:#x} offset {:#x} has {}, recompute",
+                file.get_cluster(),
+                file.get_offset(),
+                msg.join(", ")
+            ),
+        ))?;
+        if fix {
+            fix_entry_set(state, es, name_length)?;
+            state.fixed();
+        }
+        // libexfat counts a bad checksum itself when it reads the directory
+        // unless the entry set was written back
+        if checksum_only && (!fix || state.repair == crate::Repair::DryRun) {
+            state.report.set_last_uncounted();
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,143 @@
+mod dentry;
+mod duplicate;
+mod entryset;
+mod name;
+mod progress;
+mod report;
+mod rootdir;
+mod scan;
+mod time;
+mod upcase;
+
+use std::io::BufRead;
 use std::io::Write;
 
+// exit codes defined by fsck(8)
+const FSCK_OK: i32 = 0;
+const FSCK_NONDESTRUCT: i32 = 1; // errors corrected
+const FSCK_UNCORRECTED: i32 = 4; // errors left uncorrected
+const FSCK_ERROR: i32 = 8; // operational error
+const FSCK_USAGE: i32 = 16; // usage or syntax error
+const FSCK_CANCELED: i32 = 32; // checking canceled by user request
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Repair {
+    No,
+    Yes,
+    Ask,
+    DryRun,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Scan {
+    No,
+    Allocated, // clusters of files and directories
+    All,       // free clusters as well
+}
+
+#[derive(Debug)]
+struct Options {
+    repair: Repair,
+    scan: Scan,
+    undo: Option<String>,
+    dry_run: Option<String>,
+}
+
+// errors found by exfatfsck itself, libexfat keeps its own counters
+struct FsckState {
+    vol: exfat_utils::volume::Volume,
+    repair: Repair,
+    scan: Scan,
+    report: report::Report,
+    progress: progress::Progress,
+    upcase: Vec<u16>,
+    answers: std::collections::HashMap<report::ProblemType, bool>,
+    canceled: bool,
+}
+
+impl FsckState {
+    fn new(vol: exfat_utils::volume::Volume, opts: &Options, progress: progress::Progress) -> Self {
+        Self {
+            vol,
+            repair: opts.repair,
+            scan: opts.scan,
+            report: report::Report::new(),
+            progress,
+            upcase: vec![],
+            answers: std::collections::HashMap::new(),
+            canceled: false,
+        }
+    }
+
+    fn error(&mut self, problem: report::Problem) {
+        log::error!("{}", problem.get_message());
+        self.report.add(problem);
+    }
+
+    // Answers to all problems of a kind are remembered, quitting returns
+    // ECANCELED to stop checking, keeping what was fixed so far.
+    fn ask_to_fix(&mut self, problem: report::Problem) -> exfat_utils::Result<bool> {
+        let typ = problem.get_type();
+        let question = problem.get_message().to_string();
+        self.report.add(problem);
+        let answer = match self.repair {
+            Repair::No => false,
+            Repair::Yes => true,
+            Repair::DryRun => {
+                println!("{question}. Would be fixed.");
+                return Ok(true);
+            }
+            Repair::Ask => match self.answers.get(&typ) {
+                Some(v) => *v,
+                None => return self.ask(typ, &question),
+            },
+        };
+        println!(
+            "{question}. {}",
+            if answer { "Fixed." } else { "Not fixed." }
+        );
+        Ok(answer)
+    }
+
+    fn ask(&mut self, typ: report::ProblemType, question: &str) -> exfat_utils::Result<bool> {
+        loop {
+            print!("{question}. Fix (y/n/a/s/q)? ");
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+                return Ok(false);
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "y" => return Ok(true),
+                "n" => return Ok(false),
+                "a" => {
+                    self.answers.insert(typ, true);
+                    return Ok(true);
+                }
+                "s" => {
+                    self.answers.insert(typ, false);
+                    return Ok(false);
+                }
+                "q" => {
+                    self.canceled = true;
+                    return Err(Box::new(nix::errno::Errno::ECANCELED));
+                }
+                _ => println!(
+                    "y: fix, n: don't fix, a: fix all of this kind, \
+                    s: skip all of this kind, q: quit keeping fixes so far"
+                ),
+            }
+        }
+    }
+
+    fn fixed(&mut self) {
+        // nothing is written in dry-run mode
+        if self.repair != Repair::DryRun {
+            self.report.set_last_fixed();
+        }
+    }
+}
+
 fn print_version() {
     println!("Copyright (C) 2011-2023  Andrew Nayenko");
     println!("Copyright (C) 2024-  Tomohiro Kusumi");
@@ -25,7 +163,12 @@ fn print_super_block(ef: &libexfat::exfat::Exfat) {
     println!("Available space      {value:>10} {unit}");
 }
 
-fn nodeck(ef: &mut libexfat::exfat::Exfat, nid: libexfat::node::Nid) -> exfat_utils::Result<()> {
+fn nodeck(
+    ef: &mut libexfat::exfat::Exfat,
+    state: &mut FsckState,
+    nid: libexfat::node::Nid,
+    path: &str,
+) -> exfat_utils::Result<bool> {
     let cluster_size = ef.get_cluster_size();
     let node = exfat_utils::util::get_node!(ef, nid);
     let mut clusters = libexfat::div_round_up!(node.get_size(), cluster_size);
@@ -33,50 +176,56 @@ fn nodeck(ef: &mut libexfat::exfat::Exfat, nid: libexfat::node::Nid) -> exfat_ut
 
     while clusters > 0 {
         clusters -= 1;
+        state.progress.add_cluster();
         if ef.cluster_invalid(c) {
-            log::error!(
-                "file '{}' has invalid cluster {c:#x}",
-                exfat_utils::util::get_node!(ef, nid).get_name()
-            );
-            return Err(Box::new(nix::errno::Errno::EINVAL));
+            state.error(report::Problem::new(
+                report::ProblemType::InvalidCluster,
+                Some(path),
+                Some(c),
+                &format!("file '{path}' has invalid cluster {c:#x}"),
+            ));
+            return Ok(false);
         }
         if !ef.is_cluster_allocated((c - libexfat::fs::EXFAT_FIRST_DATA_CLUSTER).try_into()?)? {
-            log::error!(
-                "cluster {c:#x} of file '{}' is not allocated",
-                exfat_utils::util::get_node!(ef, nid).get_name()
-            );
-            return Err(Box::new(nix::errno::Errno::EINVAL));
+            state.error(report::Problem::new(
+                report::ProblemType::UnallocatedCluster,
+                Some(path),
+                Some(c),
+                &format!("cluster {c:#x} of file '{path}' is not allocated"),
+            ));
+            return Ok(false);
+        }
+        if state.scan != Scan::No {
+            scan::clusterck(state, path, c)?;
         }
         c = ef.next_cluster(nid, c);
     }
-    Ok(())
+    Ok(true)
 }
 
-fn dirck(ef: &mut libexfat::exfat::Exfat, path: &str) -> exfat_utils::Result<(u64, u64)> {
-    let dnid = match ef.lookup(path) {
-        Ok(v) => v,
-        Err(e) => panic!("directory '{path}' is not found: {e}"),
-    };
-    let dnode = exfat_utils::util::get_node!(ef, dnid);
-    assert!(
-        dnode.is_directory(),
-        "'{path}' is not a directory ({:#x})",
-        dnode.get_attrib()
-    );
-    if let Err(e) = nodeck(ef, dnid) {
-        exfat_utils::util::get_node_mut!(ef, dnid).put();
-        return Err(e);
+// Check a directory and its files. Subdirectories are appended to dirs
+// with their node reference held, the caller is responsible to put them.
+fn readdirck(
+    ef: &mut libexfat::exfat::Exfat,
+    state: &mut FsckState,
+    dnid: libexfat::node::Nid,
+    path: &str,
+    dirs: &mut Vec<(libexfat::node::Nid, String)>,
+) -> exfat_utils::Result<u64> {
+    if !nodeck(ef, state, dnid, path)? {
+        // don't read entries from a broken cluster chain
+        return Ok(0);
     }
+    let dnode = exfat_utils::util::get_node!(ef, dnid);
+    dentry::dentryck(
+        state,
+        path,
+        dnode.get_start_cluster(),
+        dnode.get_is_contiguous(),
+        dnode.get_size(),
+    )?;
 
-    let mut c = match ef.opendir_cursor(dnid) {
-        Ok(v) => v,
-        Err(e) => {
-            exfat_utils::util::get_node_mut!(ef, dnid).put();
-            return Err(Box::new(e));
-        }
-    };
-
-    let mut directories_count = 0;
+    let mut c = ef.opendir_cursor(dnid)?;
     let mut files_count = 0;
     loop {
         let nid = match ef.readdir_cursor(&mut c) {
@@ -88,12 +237,11 @@ fn dirck(ef: &mut libexfat::exfat::Exfat, path: &str) -> exfat_utils::Result<(u6
                     }
                 }
                 ef.closedir_cursor(c);
-                exfat_utils::util::get_node_mut!(ef, dnid).put();
                 return Err(Box::new(e));
             }
         };
         let node = exfat_utils::util::get_node!(ef, nid);
-        let entry_path = format!("{}/{}", path, node.get_name());
+        let entry_path = exfat_utils::util::get_entry_path(path, node.get_name());
         log::debug!(
             "{}: {}, {} bytes, cluster {}",
             entry_path,
@@ -106,44 +254,93 @@ fn dirck(ef: &mut libexfat::exfat::Exfat, path: &str) -> exfat_utils::Result<(u6
             node.get_start_cluster()
         );
         if node.is_directory() {
-            directories_count += 1;
-            let (d, f) = match dirck(ef, &entry_path) {
-                Ok(v) => v,
-                Err(e) => {
-                    exfat_utils::util::get_node_mut!(ef, nid).put();
-                    ef.closedir_cursor(c);
-                    exfat_utils::util::get_node_mut!(ef, dnid).put();
-                    return Err(e);
-                }
-            };
-            directories_count += d;
-            files_count += f;
-        } else {
-            files_count += 1;
-            if let Err(e) = nodeck(ef, nid) {
-                log::error!("{e}");
-            }
+            state.progress.add_directory();
+            dirs.push((nid, entry_path));
+            continue;
+        }
+        files_count += 1;
+        state.progress.add_file();
+        if let Err(e) = nodeck(ef, state, nid, &entry_path) {
+            exfat_utils::util::get_node_mut!(ef, nid).put();
+            ef.closedir_cursor(c);
+            return Err(e);
         }
         if let Err(e) = ef.flush_node(nid) {
             exfat_utils::util::get_node_mut!(ef, nid).put();
             ef.closedir_cursor(c);
-            exfat_utils::util::get_node_mut!(ef, dnid).put();
             return Err(Box::new(e));
         }
         exfat_utils::util::get_node_mut!(ef, nid).put();
     }
 
     ef.closedir_cursor(c);
-    if let Err(e) = ef.flush_node(dnid) {
+    ef.flush_node(dnid)?;
+    Ok(files_count)
+}
+
+fn dirck(
+    ef: &mut libexfat::exfat::Exfat,
+    state: &mut FsckState,
+) -> exfat_utils::Result<(u64, u64)> {
+    let rnid = match ef.lookup("") {
+        Ok(v) => v,
+        Err(e) => {
+            state.error(report::Problem::new(
+                report::ProblemType::LookupFailed,
+                Some("/"),
+                None,
+                &format!("failed to look up root directory: {e}"),
+            ));
+            return Ok((0, 0));
+        }
+    };
+
+    // walk the tree with an explicit stack of directories to visit
+    // instead of recursing and looking up each path from the root
+    let mut dirs = vec![(rnid, "/".to_string())];
+    let mut directories_count = 0;
+    let mut files_count = 0;
+    while let Some((dnid, path)) = dirs.pop() {
+        let n = dirs.len();
+        let result = readdirck(ef, state, dnid, &path, &mut dirs);
         exfat_utils::util::get_node_mut!(ef, dnid).put();
-        return Err(Box::new(e));
+        match result {
+            Ok(v) => {
+                directories_count += u64::try_from(dirs.len() - n)?;
+                files_count += v;
+                // visit subdirectories in the order they were read
+                dirs[n..].reverse();
+            }
+            Err(e) => {
+                for (nid, _) in dirs {
+                    exfat_utils::util::get_node_mut!(ef, nid).put();
+                }
+                return Err(e);
+            }
+        }
     }
-    exfat_utils::util::get_node_mut!(ef, dnid).put();
-
     Ok((directories_count, files_count))
 }
 
-fn fsck(spec: &str, mopt: &[&str]) -> exfat_utils::Result<Option<libexfat::exfat::Exfat>> {
+fn checkall(
+    ef: &mut libexfat::exfat::Exfat,
+    state: &mut FsckState,
+) -> exfat_utils::Result<(u64, u64)> {
+    rootdir::rootdirck(state)?;
+    upcase::upcaseck(state)?;
+    let counts = dirck(ef, state)?;
+    if state.scan == Scan::All {
+        scan::freeck(ef, state)?;
+    }
+    Ok(counts)
+}
+
+fn fsck(
+    spec: &str,
+    mopt: &[&str],
+    opts: &Options,
+    progress: progress::Progress,
+) -> exfat_utils::Result<Option<(libexfat::exfat::Exfat, FsckState)>> {
     // ENODEV - failed to open the device, checking haven't started
     let mut ef = match libexfat::mount(spec, mopt) {
         Ok(v) => v,
@@ -160,27 +357,119 @@ fn fsck(spec: &str, mopt: &[&str]) -> exfat_utils::Result<Option<libexfat::exfat
         }
     };
 
+    let mut vol = exfat_utils::volume::Volume::open(
+        spec,
+        if opts.repair == Repair::No || opts.repair == Repair::DryRun {
+            "ro"
+        } else {
+            "rw"
+        },
+    )?;
+    if let Some(f) = &opts.dry_run {
+        vol.set_dry_run(if f == "-" {
+            Box::new(std::io::stdout())
+        } else {
+            Box::new(std::fs::File::create(f)?)
+        });
+    }
+    if let Some(f) = &opts.undo {
+        vol.set_undo(exfat_utils::undo::Undo::create(
+            f,
+            u32::from_le(vol.get_super_block().volume_serial),
+            vol.get_sector_size(),
+        )?);
+    }
+    let mut state = FsckState::new(vol, opts, progress);
+    state.report.set_geometry(report::Geometry::new(&ef)?);
+    state.progress.set_clusters_total(
+        u64::from(u32::from_le(ef.get_super_block().cluster_count))
+            - u64::from(ef.get_free_clusters()?),
+    );
+
     print_super_block(&ef);
+    // libexfat marks the volume dirty while checking, cleared explicitly
+    // after a successful check
+    let sector_size = state.vol.get_sector_size();
+    state.vol.save_undo(0, sector_size)?;
     ef.soil_super_block()?;
-    let (directories_count, files_count) = dirck(&mut ef, "")?;
+    let (directories_count, files_count) = match checkall(&mut ef, &mut state) {
+        Ok(v) => v,
+        Err(e) => {
+            if !state.canceled {
+                return Err(e);
+            }
+            state.progress.finish();
+            print!("File system checking canceled. ");
+            std::io::stdout().flush()?;
+            return Ok(Some((ef, state)));
+        }
+    };
+    state.report.set_counts(directories_count, files_count);
+    state.progress.finish();
 
     println!("Totally {directories_count} directories and {files_count} files.");
     print!("File system checking finished. ");
     std::io::stdout().flush()?;
-    Ok(Some(ef))
+    Ok(Some((ef, state)))
+}
+
+// Volume flags serve as what other fscks call the clean flag.
+fn is_clean(spec: &str) -> exfat_utils::Result<bool> {
+    let vol = exfat_utils::volume::Volume::open(spec, "ro")?;
+    Ok(vol.get_volume_state()
+        & (exfat_utils::volume::VOLUME_FLAG_DIRTY | exfat_utils::volume::VOLUME_FLAG_MEDIA_FAILURE)
+        == 0)
+}
+
+// MediaFailure is only known to be up to date if clusters were scanned
+fn update_volume_state(state: &mut FsckState, clean: bool) -> exfat_utils::Result<()> {
+    let mut volume_state = state.vol.get_volume_state();
+    if clean {
+        volume_state &= !exfat_utils::volume::VOLUME_FLAG_DIRTY;
+    } else {
+        volume_state |= exfat_utils::volume::VOLUME_FLAG_DIRTY;
+    }
+    if state.scan != Scan::No {
+        if state.report.has_media_failure() {
+            volume_state |= exfat_utils::volume::VOLUME_FLAG_MEDIA_FAILURE;
+        } else {
+            volume_state &= !exfat_utils::volume::VOLUME_FLAG_MEDIA_FAILURE;
+        }
+    }
+    state.vol.set_volume_state(volume_state)?;
+    state.vol.fsync()
+}
+
+fn write_report(
+    f: &str,
+    spec: &str,
+    report: &report::Report,
+    status: &str,
+    errors: usize,
+    errors_fixed: usize,
+) {
+    let s = report
+        .to_json(spec, status, errors, errors_fixed)
+        .to_pretty_string();
+    if let Err(e) = std::fs::write(f, s + "\n") {
+        log::error!("failed to write report to {f}: {e}");
+    }
 }
 
 fn usage(prog: &str, gopt: &getopts::Options) {
     print!(
         "{}",
-        gopt.usage(&format!("Usage: {prog} [-a | -n | -p | -y] [-V] <device>"))
+        gopt.usage(&format!(
+            "Usage: {prog} [-a | -n | -p | -y] [-f] [-c [--scan-free]] [-C <fd>] [--progress] [--json <file>] [-z <undo-file>] [--dry-run <file>] [-V] <device>"
+        ))
     );
 }
 
+#[allow(clippy::too_many_lines)]
 fn main() {
     if let Err(e) = exfat_utils::util::init_std_logger() {
         eprintln!("{e}");
-        std::process::exit(1);
+        std::process::exit(FSCK_ERROR);
     }
 
     let args: Vec<String> = std::env::args().collect();
@@ -202,6 +491,66 @@ fn main() {
     );
     gopt.optflag("p", "", "Same as -a for compatibility with other *fsck.");
     gopt.optflag("y", "", "Same as -a for compatibility with other *fsck.");
+    gopt.optflag(
+        "f",
+        "",
+        "Force checking even if the file system seems clean. \
+        With -p, a volume without the dirty or media failure flag \
+        set is skipped otherwise.",
+    );
+    gopt.optflag(
+        "c",
+        "",
+        "Read every cluster of files and directories, and report \
+        unreadable ones with the file owning them.",
+    );
+    gopt.optflag(
+        "",
+        "scan-free",
+        "With -c, read free clusters as well, and mark unreadable ones \
+        bad in the FAT and the allocation bitmap in repair mode.",
+    );
+    gopt.optopt(
+        "C",
+        "",
+        "Write completion information to the given file descriptor, \
+        one \"pass current max device\" line at a time as e2fsck does, \
+        so that progress of the check can be monitored. 0 is the same \
+        as --progress.",
+        "<fd>",
+    );
+    gopt.optflag(
+        "",
+        "progress",
+        "Report directories and files processed and clusters visited \
+        out of allocated clusters to stderr.",
+    );
+    gopt.optopt(
+        "",
+        "json",
+        "Write a JSON report of the volume geometry, counts and \
+        each problem found to the given file.",
+        "<file>",
+    );
+    gopt.optopt(
+        "z",
+        "",
+        "Save original contents of sectors to the given file before \
+        overwriting them, so that exfatundo can restore the device. \
+        Repairs done by libexfat itself are disabled, since its writes \
+        can't be saved.",
+        "<undo-file>",
+    );
+    gopt.optopt(
+        "",
+        "dry-run",
+        "Run repairs as -y does, but log each write with its offset, \
+        length, structure and a hexdump of before and after contents \
+        to the given file (- for stdout) instead of writing to the device. \
+        libexfat is mounted read-only, so its own repairs are disabled and \
+        what it finds is only counted.",
+        "<file>",
+    );
     gopt.optflag("V", "version", "Print version and copyright.");
     gopt.optflag("h", "help", "Print usage.");
 
@@ -210,16 +559,16 @@ fn main() {
         Err(e) => {
             log::error!("{e}");
             usage(prog, &gopt);
-            std::process::exit(1);
+            std::process::exit(FSCK_USAGE);
         }
     };
     if matches.opt_present("V") {
         print_version();
-        std::process::exit(0);
+        std::process::exit(FSCK_OK);
     }
     if matches.opt_present("help") {
         usage(prog, &gopt);
-        std::process::exit(0);
+        std::process::exit(FSCK_OK);
     }
 
     let mut mopt = vec![];
@@ -227,51 +576,137 @@ fn main() {
         mopt.push("--debug");
     }
 
-    if matches.opt_present("a") || matches.opt_present("p") || matches.opt_present("y") {
-        mopt.extend_from_slice(&["--repair", "yes"]);
+    let dry_run = matches.opt_str("dry-run");
+    let repair = if dry_run.is_some() {
+        Repair::DryRun
+    } else if matches.opt_present("a") || matches.opt_present("p") || matches.opt_present("y") {
+        Repair::Yes
     } else if matches.opt_present("n") {
-        mopt.extend_from_slice(&["--repair", "no", "--mode", "ro"]);
+        Repair::No
     } else {
-        let repair = match nix::unistd::isatty(0) {
+        match nix::unistd::isatty(0) {
             Ok(v) => {
                 if v {
-                    "ask"
+                    Repair::Ask
                 } else {
-                    "no"
+                    Repair::No
                 }
             }
             Err(e) => {
                 log::error!("{e}");
-                std::process::exit(1);
+                std::process::exit(FSCK_ERROR);
             }
-        };
-        mopt.extend_from_slice(&["--repair", repair]);
+        }
+    };
+    // libexfat writes don't go through the undo file or the dry-run log
+    let undo = matches.opt_str("z");
+    mopt.extend_from_slice(&[
+        "--repair",
+        match if undo.is_some() { Repair::No } else { repair } {
+            Repair::No | Repair::DryRun => "no",
+            Repair::Yes => "yes",
+            Repair::Ask => "ask",
+        },
+    ]);
+    if matches.opt_present("n") || repair == Repair::DryRun {
+        mopt.extend_from_slice(&["--mode", "ro"]);
     }
 
+    let scan = if !matches.opt_present("c") {
+        Scan::No
+    } else if matches.opt_present("scan-free") {
+        Scan::All
+    } else {
+        Scan::Allocated
+    };
+    let preen = matches.opt_present("p");
+    let force = matches.opt_present("f");
+    let json = matches.opt_str("json");
+    let mut verbose_progress = matches.opt_present("progress");
+    let fd = match matches.opt_str("C") {
+        Some(v) => match v.parse::<i32>() {
+            // e2fsck draws a completion bar instead of writing to stdin
+            Ok(0) => {
+                verbose_progress = true;
+                None
+            }
+            Ok(v) if v > 0 => Some(v),
+            _ => {
+                log::error!("invalid option value: '{v}'");
+                usage(prog, &gopt);
+                std::process::exit(FSCK_USAGE);
+            }
+        },
+        None => None,
+    };
+
     let args = matches.free;
     if args.len() != 1 {
         usage(prog, &gopt);
-        std::process::exit(1);
+        std::process::exit(FSCK_USAGE);
     }
     let spec = &args[0];
 
+    if preen && !force {
+        if let Ok(true) = is_clean(spec) {
+            println!("{spec}: clean, skipping.");
+            if let Some(f) = json {
+                write_report(&f, spec, &report::Report::new(), "skipped", 0, 0);
+            }
+            std::process::exit(FSCK_OK);
+        }
+    }
+
     println!("Checking file system on {spec}.");
-    let ef = match fsck(spec, &mopt) {
+    let progress = progress::Progress::new(verbose_progress, fd, spec);
+    let opts = Options {
+        repair,
+        scan,
+        undo,
+        dry_run,
+    };
+    let result = match fsck(spec, &mopt, &opts, progress) {
         Ok(v) => v,
         Err(e) => {
             log::error!("{e}");
-            std::process::exit(1);
+            std::process::exit(FSCK_ERROR);
+        }
+    };
+    let Some((ef, mut state)) = result else {
+        // checking stopped, the file system couldn't be mounted
+        println!();
+        if let Some(f) = json {
+            write_report(&f, spec, &report::Report::new(), "stopped", 0, 0);
         }
+        std::process::exit(FSCK_UNCORRECTED);
+    };
+    let errors = ef.get_errors() + state.report.get_errors();
+    let errors_fixed = ef.get_errors_fixed() + state.report.get_errors_fixed();
+    let (status, code) = if state.canceled {
+        ("canceled", FSCK_CANCELED)
+    } else if errors == 0 {
+        ("clean", FSCK_OK)
+    } else if errors_fixed < errors {
+        ("uncorrected", FSCK_UNCORRECTED)
+    } else {
+        ("fixed", FSCK_NONDESTRUCT)
     };
-    if let Some(ef) = ef {
-        if ef.get_errors() != 0 {
-            log::error!(
-                "ERRORS FOUND: {}, FIXED: {}.",
-                ef.get_errors(),
-                ef.get_errors_fixed()
-            );
-            std::process::exit(1);
+    if let Some(f) = json {
+        write_report(&f, spec, &state.report, status, errors, errors_fixed);
+    }
+    // libexfat writes back its copy of the boot sector on unmount
+    drop(ef);
+    // in dry-run mode the volume flags write only goes to the log
+    if matches!(state.repair, Repair::Yes | Repair::Ask | Repair::DryRun) {
+        if let Err(e) = update_volume_state(&mut state, code == FSCK_OK || code == FSCK_NONDESTRUCT)
+        {
+            log::error!("{e}");
+            std::process::exit(FSCK_ERROR);
         }
     }
+    if errors != 0 {
+        log::error!("ERRORS FOUND: {errors}, FIXED: {errors_fixed}.");
+        std::process::exit(code);
+    }
     println!("No errors found.");
 }
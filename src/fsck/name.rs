@@ -0,0 +1,159 @@
+const ILLEGAL_CHARS: &str = "\"*/:<>?\\|";
+const REPLACEMENT_CHAR: u16 = 0x5f; // '_'
+const EMPTY_NAME: &str = "noname";
+
+fn is_illegal_char(c: u16) -> bool {
+    c < 0x20 || ILLEGAL_CHARS.encode_utf16().any(|x| x == c)
+}
+
+fn is_high_surrogate(c: u16) -> bool {
+    (0xd800..=0xdbff).contains(&c)
+}
+
+fn is_low_surrogate(c: u16) -> bool {
+    (0xdc00..=0xdfff).contains(&c)
+}
+
+fn has_illegal_char(name: &[u16]) -> bool {
+    name.iter().any(|c| is_illegal_char(*c))
+}
+
+fn has_unpaired_surrogate(name: &[u16]) -> bool {
+    let mut i = 0;
+    while i < name.len() {
+        if is_high_surrogate(name[i]) {
+            if i + 1 == name.len() || !is_low_surrogate(name[i + 1]) {
+                return true;
+            }
+            i += 2;
+        } else if is_low_surrogate(name[i]) {
+            return true;
+        } else {
+            i += 1;
+        }
+    }
+    false
+}
+
+// Replace illegal characters and unpaired surrogates one to one,
+// so that the name still fits in existing file name entries.
+fn sanitize(name: &[u16]) -> Vec<u16> {
+    let mut v = name.to_vec();
+    let mut i = 0;
+    while i < v.len() {
+        if is_high_surrogate(v[i]) && i + 1 < v.len() && is_low_surrogate(v[i + 1]) {
+            i += 2;
+            continue;
+        }
+        if is_illegal_char(v[i]) || is_high_surrogate(v[i]) || is_low_surrogate(v[i]) {
+            v[i] = REPLACEMENT_CHAR;
+        }
+        i += 1;
+    }
+    v
+}
+
+fn fix_name(
+    state: &mut crate::FsckState,
+    es: &mut exfat_utils::entry::EntrySet,
+    name: &[u16],
+) -> exfat_utils::Result<bool> {
+    let name = if name.is_empty() {
+        EMPTY_NAME.encode_utf16().collect()
+    } else {
+        sanitize(name)
+    };
+    if name.len() > es.get_name_entries_count() * exfat_utils::entry::NAME_CHARS_PER_ENTRY {
+        return Ok(false);
+    }
+    es.set_name(&name);
+    let hash = exfat_utils::entry::calc_name_hash(&state.upcase, &name);
+    if let Some(v) = es.get_stream_mut() {
+        v.set_u16(exfat_utils::entry::STREAM_NAME_HASH, hash);
+    }
+    es.update_checksum();
+    crate::dentry::write_entry_set(&mut state.vol, es)?;
+    Ok(true)
+}
+
+pub(crate) fn nameck(
+    state: &mut crate::FsckState,
+    path: &str,
+    sets: &mut [exfat_utils::entry::EntrySet],
+) -> exfat_utils::Result<()> {
+    for es in sets {
+        let Some(stream) = es.get_stream() else {
+            continue;
+        };
+        let length = usize::from(stream.get_u8(exfat_utils::entry::STREAM_NAME_LENGTH));
+        let hash = stream.get_u16(exfat_utils::entry::STREAM_NAME_HASH);
+        let chars = es.get_name_chars();
+        let entries = es.get_name_entries_count();
+
+        // trust characters actually stored if the length doesn't match
+        // the number of file name entries
+        let mut problems = vec![];
+        let name = if length != 0
+            && length <= chars.len()
+            && libexfat::div_round_up!(length, exfat_utils::entry::NAME_CHARS_PER_ENTRY) == entries
+        {
+            chars[..length].to_vec()
+        } else {
+            let n = std::cmp::min(
+                libexfat::utf::utf16_length(&chars),
+                libexfat::fs::EXFAT_NAME_MAX,
+            );
+            // a length mismatch itself is reported by entrysetck
+            if n == 0 {
+                problems.push((
+                    crate::report::ProblemType::NameEmpty,
+                    "empty name".to_string(),
+                ));
+            }
+            chars[..n].to_vec()
+        };
+        if has_illegal_char(&name) {
+            problems.push((
+                crate::report::ProblemType::NameIllegalChar,
+                "illegal character".to_string(),
+            ));
+        }
+        if has_unpaired_surrogate(&name) {
+            problems.push((
+                crate::report::ProblemType::NameInvalidUtf16,
+                "unpaired surrogate".to_string(),
+            ));
+        }
+        let expected = exfat_utils::entry::calc_name_hash(&state.upcase, &name);
+        if problems.is_empty() && hash != expected {
+            problems.push((
+                crate::report::ProblemType::NameHash,
+                format!("name hash {hash:#06x} mismatches {expected:#06x}"),
+            ));
+        }
+        let Some((typ, _)) = problems.first() else {
+            continue;
+        };
+
+        let s = crate::dentry::get_name_string(&name);
+        let msg: Vec<&str> = problems.iter().map(|x| x.1.as_str()).collect();
+        let question = if *typ == crate::report::ProblemType::NameHash {
+            format!("'{s}' in '{path}' has {}, recompute", msg.join(", "))
+        } else {
+            format!("'{s}' in '{path}' has {}, rename", msg.join(", "))
+        };
+        if state.ask_to_fix(crate::report::Problem::new(
+            *typ,
+            Some(&exfat_utils::util::get_entry_path(path, &s)),
+            crate::dentry::get_start_cluster(es),
+            &question,
+        ))? {
+            if fix_name(state, es, &name)? {
+                state.fixed();
+            } else {
+                log::error!("failed to fix '{s}' in '{path}'");
+            }
+        }
+    }
+    Ok(())
+}
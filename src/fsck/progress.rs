@@ -0,0 +1,102 @@
+use std::io::Write;
+use std::os::fd::FromRawFd;
+
+// e2fsck has multiple passes, exfatfsck walks the tree once
+const PASS: u32 = 1;
+
+#[derive(Debug)]
+pub(crate) struct Progress {
+    verbose: bool,
+    // not closed on drop, the descriptor belongs to the caller
+    fp: Option<std::mem::ManuallyDrop<std::fs::File>>,
+    spec: String,
+    directories_count: u64,
+    files_count: u64,
+    clusters: u64,
+    clusters_total: u64,
+    last: Option<std::time::Instant>,
+}
+
+impl Progress {
+    pub(crate) fn new(verbose: bool, fd: Option<i32>, spec: &str) -> Self {
+        Self {
+            verbose,
+            fp: fd.map(|fd| std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) })),
+            spec: spec.to_string(),
+            directories_count: 0,
+            files_count: 0,
+            clusters: 0,
+            clusters_total: 0,
+            last: None,
+        }
+    }
+
+    pub(crate) fn set_clusters_total(&mut self, clusters_total: u64) {
+        self.clusters_total = clusters_total;
+    }
+
+    pub(crate) fn add_directory(&mut self) {
+        self.directories_count += 1;
+        self.update(false);
+    }
+
+    pub(crate) fn add_file(&mut self) {
+        self.files_count += 1;
+        self.update(false);
+    }
+
+    pub(crate) fn add_cluster(&mut self) {
+        self.clusters += 1;
+        self.update(false);
+    }
+
+    pub(crate) fn finish(&mut self) {
+        // clusters of the bitmap and upcase table aren't visited
+        self.clusters = self.clusters_total;
+        self.update(true);
+        if self.verbose {
+            eprintln!();
+        }
+    }
+
+    fn update(&mut self, force: bool) {
+        if !self.verbose && self.fp.is_none() {
+            return;
+        }
+        let now = std::time::Instant::now();
+        if !force {
+            if let Some(last) = self.last {
+                if now.duration_since(last) < std::time::Duration::from_millis(200) {
+                    return;
+                }
+            }
+        }
+        self.last = Some(now);
+
+        let clusters = std::cmp::min(self.clusters, self.clusters_total);
+        if self.verbose {
+            let permille = (clusters * 1000)
+                .checked_div(self.clusters_total)
+                .unwrap_or(1000);
+            eprint!(
+                "\r{} directories, {} files, {clusters}/{} clusters ({}.{}%)",
+                self.directories_count,
+                self.files_count,
+                self.clusters_total,
+                permille / 10,
+                permille % 10
+            );
+        }
+        if let Some(fp) = &mut self.fp {
+            // same format as e2fsck -C, "pass current max device"
+            if let Err(e) = writeln!(
+                fp,
+                "{PASS} {clusters} {} {}",
+                self.clusters_total, self.spec
+            ) {
+                log::error!("failed to write progress: {e}");
+                self.fp = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum ProblemType {
+    LookupFailed,
+    InvalidCluster,
+    UnallocatedCluster,
+    UpcaseMissing,
+    UpcaseUnreadable,
+    UpcaseChecksum,
+    UpcaseInvalid,
+    DuplicateName,
+    NameEmpty,
+    NameLength,
+    NameIllegalChar,
+    NameInvalidUtf16,
+    NameHash,
+    TimestampInvalid,
+    UtcOffsetInvalid,
+    SpecialEntryCount,
+    SpecialEntryInvalid,
+    UnknownCriticalEntry,
+    UnreadableCluster,
+    UnreadableFreeCluster,
+    EntrySetStream,
+    EntrySetSecondaryCount,
+    EntrySetChecksum,
+}
+
+impl ProblemType {
+    // stable identifiers for the JSON report
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::LookupFailed => "lookup_failed",
+            Self::InvalidCluster => "invalid_cluster",
+            Self::UnallocatedCluster => "unallocated_cluster",
+            Self::UpcaseMissing => "upcase_missing",
+            Self::UpcaseUnreadable => "upcase_unreadable",
+            Self::UpcaseChecksum => "upcase_checksum",
+            Self::UpcaseInvalid => "upcase_invalid",
+            Self::DuplicateName => "duplicate_name",
+            Self::NameEmpty => "name_empty",
+            Self::NameLength => "name_length",
+            Self::NameIllegalChar => "name_illegal_char",
+            Self::NameInvalidUtf16 => "name_invalid_utf16",
+            Self::NameHash => "name_hash",
+            Self::TimestampInvalid => "timestamp_invalid",
+            Self::UtcOffsetInvalid => "utc_offset_invalid",
+            Self::SpecialEntryCount => "special_entry_count",
+            Self::SpecialEntryInvalid => "special_entry_invalid",
+            Self::UnknownCriticalEntry => "unknown_critical_entry",
+            Self::UnreadableCluster => "unreadable_cluster",
+            Self::UnreadableFreeCluster => "unreadable_free_cluster",
+            Self::EntrySetStream => "entry_set_stream",
+            Self::EntrySetSecondaryCount => "entry_set_secondary_count",
+            Self::EntrySetChecksum => "entry_set_checksum",
+        }
+    }
+}
+
+// Path is the file the problem is about, none for volume structures.
+// Cluster is a data cluster, the one at fault for problems in data and
+// the first one of the file or structure for problems in its directory
+// entry, none if it has no data. Entry locations are in the message.
+#[derive(Debug)]
+pub(crate) struct Problem {
+    typ: ProblemType,
+    path: Option<String>,
+    cluster: Option<u32>,
+    message: String,
+    fixed: bool,
+    counted: bool,
+}
+
+impl Problem {
+    pub(crate) fn new(
+        typ: ProblemType,
+        path: Option<&str>,
+        cluster: Option<u32>,
+        message: &str,
+    ) -> Self {
+        Self {
+            typ,
+            path: path.map(str::to_string),
+            cluster,
+            message: message.to_string(),
+            fixed: false,
+            counted: true,
+        }
+    }
+
+    pub(crate) fn get_type(&self) -> ProblemType {
+        self.typ
+    }
+
+    pub(crate) fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    fn to_json(&self) -> exfat_utils::json::Value {
+        let mut v = exfat_utils::json::Value::new_object();
+        v.insert("type", self.typ.as_str());
+        v.insert("path", self.path.clone());
+        v.insert("cluster", self.cluster);
+        v.insert("fixed", self.fixed);
+        v.insert("counted", self.counted);
+        v.insert("message", self.message.as_str());
+        v
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Geometry {
+    version_major: u8,
+    version_minor: u8,
+    sector_size: u64,
+    cluster_size: u64,
+    sector_count: u64,
+    cluster_count: u32,
+    volume_size: u64,
+    used_space: u64,
+    available_space: u64,
+}
+
+impl Geometry {
+    pub(crate) fn new(ef: &libexfat::exfat::Exfat) -> exfat_utils::Result<Self> {
+        let sb = ef.get_super_block();
+        let volume_size = u64::from_le(sb.sector_count) * sb.get_sector_size();
+        let available_space = u64::from(ef.get_free_clusters()?) * sb.get_cluster_size();
+        Ok(Self {
+            version_major: sb.version_major,
+            version_minor: sb.version_minor,
+            sector_size: sb.get_sector_size(),
+            cluster_size: sb.get_cluster_size(),
+            sector_count: u64::from_le(sb.sector_count),
+            cluster_count: u32::from_le(sb.cluster_count),
+            volume_size,
+            used_space: volume_size - available_space,
+            available_space,
+        })
+    }
+
+    fn to_json(&self) -> exfat_utils::json::Value {
+        let mut v = exfat_utils::json::Value::new_object();
+        v.insert(
+            "version",
+            format!("{}.{}", self.version_major, self.version_minor),
+        );
+        v.insert("sector_size", self.sector_size);
+        v.insert("cluster_size", self.cluster_size);
+        v.insert("sector_count", self.sector_count);
+        v.insert("cluster_count", self.cluster_count);
+        v.insert("volume_size", self.volume_size);
+        v.insert("used_space", self.used_space);
+        v.insert("available_space", self.available_space);
+        v
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    geometry: Option<Geometry>,
+    directories_count: u64,
+    files_count: u64,
+    problems: Vec<Problem>,
+}
+
+impl Report {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn set_geometry(&mut self, geometry: Geometry) {
+        self.geometry = Some(geometry);
+    }
+
+    pub(crate) fn set_counts(&mut self, directories_count: u64, files_count: u64) {
+        self.directories_count = directories_count;
+        self.files_count = files_count;
+    }
+
+    pub(crate) fn add(&mut self, problem: Problem) {
+        self.problems.push(problem);
+    }
+
+    pub(crate) fn set_last_fixed(&mut self) {
+        if let Some(p) = self.problems.last_mut() {
+            p.fixed = true;
+        }
+    }
+
+    // problems libexfat also finds are left out of the counters here,
+    // they are already in the libexfat ones
+    pub(crate) fn set_last_uncounted(&mut self) {
+        if let Some(p) = self.problems.last_mut() {
+            p.counted = false;
+        }
+    }
+
+    pub(crate) fn get_errors(&self) -> usize {
+        self.problems.iter().filter(|p| p.counted).count()
+    }
+
+    // unreadable clusters which aren't marked bad in the FAT
+    pub(crate) fn has_media_failure(&self) -> bool {
+        self.problems.iter().any(|p| {
+            !p.fixed
+                && (p.typ == ProblemType::UnreadableCluster
+                    || p.typ == ProblemType::UnreadableFreeCluster)
+        })
+    }
+
+    pub(crate) fn get_errors_fixed(&self) -> usize {
+        self.problems
+            .iter()
+            .filter(|p| p.counted && p.fixed)
+            .count()
+    }
+
+    // libexfat only exposes counters for what it finds during mount and
+    // node loading, those are reported as totals without details
+    pub(crate) fn to_json(
+        &self,
+        spec: &str,
+        status: &str,
+        errors: usize,
+        errors_fixed: usize,
+    ) -> exfat_utils::json::Value {
+        let mut v = exfat_utils::json::Value::new_object();
+        v.insert("device", spec);
+        v.insert("status", status);
+        v.insert(
+            "geometry",
+            match &self.geometry {
+                Some(v) => v.to_json(),
+                None => exfat_utils::json::Value::Null,
+            },
+        );
+        v.insert("directories", self.directories_count);
+        v.insert("files", self.files_count);
+        v.insert("errors", errors);
+        v.insert("errors_fixed", errors_fixed);
+        v.insert(
+            "problems",
+            exfat_utils::json::Value::Array(self.problems.iter().map(Problem::to_json).collect()),
+        );
+        v
+    }
+}
@@ -0,0 +1,169 @@
+const UPCASE_SIZE_MAX: u64 = 0x20000; // 2 bytes for each of 0x10000 characters
+
+#[derive(Debug, Default)]
+struct SpecialEntries {
+    bitmap: Vec<exfat_utils::entry::Slot>,
+    upcase: Vec<exfat_utils::entry::Slot>,
+    label: Vec<exfat_utils::entry::Slot>,
+    guid: Vec<exfat_utils::entry::Slot>,
+    unknown: Vec<exfat_utils::entry::Slot>,
+}
+
+// in use, primary and critical
+fn is_critical_primary(typ: u8) -> bool {
+    typ & (libexfat::fs::EXFAT_ENTRY_VALID
+        | libexfat::fs::EXFAT_ENTRY_CONTINUED
+        | libexfat::fs::EXFAT_ENTRY_OPTIONAL)
+        == libexfat::fs::EXFAT_ENTRY_VALID
+}
+
+fn get_special_entries(
+    vol: &mut exfat_utils::volume::Volume,
+) -> exfat_utils::Result<SpecialEntries> {
+    let mut entries = SpecialEntries::default();
+    for slot in vol.read_root_entries()? {
+        match slot.get_type() {
+            libexfat::fs::EXFAT_ENTRY_BITMAP => entries.bitmap.push(slot),
+            libexfat::fs::EXFAT_ENTRY_UPCASE => entries.upcase.push(slot),
+            libexfat::fs::EXFAT_ENTRY_LABEL => entries.label.push(slot),
+            exfat_utils::entry::EXFAT_ENTRY_GUID => entries.guid.push(slot),
+            libexfat::fs::EXFAT_ENTRY_FILE => (),
+            x if is_critical_primary(x) => entries.unknown.push(slot),
+            _ => (),
+        }
+    }
+    Ok(entries)
+}
+
+fn countck(state: &mut crate::FsckState, name: &str, slots: &[exfat_utils::entry::Slot]) {
+    if slots.len() > 1 {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryCount,
+            None,
+            None,
+            &format!(
+                "root directory has {} {name} entries, the second at offset {:#x}",
+                slots.len(),
+                slots[1].get_offset()
+            ),
+        ));
+    }
+}
+
+fn bitmapck(state: &mut crate::FsckState, slot: &exfat_utils::entry::Slot) {
+    let start_cluster = slot.get_u32(exfat_utils::entry::BITMAP_START_CLUSTER);
+    let size = slot.get_u64(exfat_utils::entry::BITMAP_SIZE);
+    let expected = libexfat::div_round_up!(u64::from(state.vol.get_cluster_count()), 8);
+    if size != expected {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryInvalid,
+            None,
+            Some(start_cluster),
+            &format!("allocation bitmap size {size} mismatches {expected} for cluster count"),
+        ));
+    }
+    if let Err(e) = state
+        .vol
+        .get_cluster_chain(start_cluster, false, Some(size))
+    {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryInvalid,
+            None,
+            Some(start_cluster),
+            &format!("allocation bitmap at cluster {start_cluster:#x} is unreadable: {e}"),
+        ));
+    }
+}
+
+// contents of the table are checked by upcaseck
+fn upcaseck(state: &mut crate::FsckState, slot: &exfat_utils::entry::Slot) {
+    let start_cluster = slot.get_u32(exfat_utils::entry::UPCASE_START_CLUSTER);
+    let size = slot.get_u64(exfat_utils::entry::UPCASE_SIZE);
+    if state.vol.cluster_invalid(start_cluster) || size == 0 || size > UPCASE_SIZE_MAX {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryInvalid,
+            None,
+            Some(start_cluster),
+            &format!("upcase table entry has start cluster {start_cluster:#x} and size {size}"),
+        ));
+    }
+}
+
+fn labelck(state: &mut crate::FsckState, slot: &exfat_utils::entry::Slot) {
+    let length = usize::from(slot.get_u8(exfat_utils::entry::LABEL_LENGTH));
+    if length > exfat_utils::entry::LABEL_CHARS_MAX {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryInvalid,
+            None,
+            None,
+            &format!(
+                "volume label length {length} at offset {:#x} exceeds {}",
+                slot.get_offset(),
+                exfat_utils::entry::LABEL_CHARS_MAX
+            ),
+        ));
+    }
+}
+
+fn guidck(state: &mut crate::FsckState, slot: &exfat_utils::entry::Slot) {
+    let count = slot.get_u8(exfat_utils::entry::GUID_SECONDARY_COUNT);
+    if count != 0 {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryInvalid,
+            None,
+            None,
+            &format!(
+                "volume GUID entry at offset {:#x} has {count} secondary entries",
+                slot.get_offset()
+            ),
+        ));
+    }
+}
+
+// libexfat mount stops at the first entry of each kind and ignores
+// entries it doesn't know, check what it accepted as is.
+pub(crate) fn rootdirck(state: &mut crate::FsckState) -> exfat_utils::Result<()> {
+    let entries = get_special_entries(&mut state.vol)?;
+
+    if entries.bitmap.is_empty() {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::SpecialEntryCount,
+            None,
+            None,
+            "allocation bitmap entry is missing",
+        ));
+    }
+    // a missing upcase table is reported by upcaseck
+    countck(state, "allocation bitmap", &entries.bitmap);
+    countck(state, "upcase table", &entries.upcase);
+    countck(state, "volume label", &entries.label);
+    countck(state, "volume GUID", &entries.guid);
+
+    if let Some(slot) = entries.bitmap.first() {
+        bitmapck(state, slot);
+    }
+    if let Some(slot) = entries.upcase.first() {
+        upcaseck(state, slot);
+    }
+    if let Some(slot) = entries.label.first() {
+        labelck(state, slot);
+    }
+    if let Some(slot) = entries.guid.first() {
+        guidck(state, slot);
+    }
+
+    // the spec requires to refuse mounting a volume with these
+    for slot in &entries.unknown {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::UnknownCriticalEntry,
+            None,
+            None,
+            &format!(
+                "unknown critical primary entry {:#04x} at offset {:#x}",
+                slot.get_type(),
+                slot.get_offset()
+            ),
+        ));
+    }
+    Ok(())
+}
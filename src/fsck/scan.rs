@@ -0,0 +1,75 @@
+pub(crate) fn clusterck(
+    state: &mut crate::FsckState,
+    path: &str,
+    cluster: u32,
+) -> exfat_utils::Result<()> {
+    if !state.vol.is_cluster_readable(cluster)? {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::UnreadableCluster,
+            Some(path),
+            Some(cluster),
+            &format!("cluster {cluster:#x} of file '{path}' is unreadable"),
+        ));
+    }
+    Ok(())
+}
+
+// libexfat keeps its own copy of the bitmap, which it only writes back
+// if it allocated or freed clusters
+fn mark_bad(state: &mut crate::FsckState, cluster: u32) -> exfat_utils::Result<bool> {
+    let Some(slot) = state
+        .vol
+        .find_root_entry(libexfat::fs::EXFAT_ENTRY_BITMAP)?
+    else {
+        return Ok(false);
+    };
+    let start_cluster = slot.get_u32(exfat_utils::entry::BITMAP_START_CLUSTER);
+    let size = slot.get_u64(exfat_utils::entry::BITMAP_SIZE);
+    let clusters = state
+        .vol
+        .get_cluster_chain(start_cluster, false, Some(size))?;
+    let index = u64::from(cluster - libexfat::fs::EXFAT_FIRST_DATA_CLUSTER);
+    let cluster_size = state.vol.get_cluster_size();
+    let Some(c) = clusters.get(usize::try_from(index / 8 / cluster_size)?) else {
+        return Ok(false);
+    };
+    let offset = state.vol.c2o(*c) + index / 8 % cluster_size;
+    let mut buf = state.vol.pread(1, offset)?;
+    buf[0] |= 1 << (index % 8);
+
+    state
+        .vol
+        .set_fat_entry(cluster, libexfat::fs::EXFAT_CLUSTER_BAD)?;
+    state
+        .vol
+        .pwrite_structure(&buf, offset, "allocation bitmap")?;
+    Ok(true)
+}
+
+pub(crate) fn freeck(
+    ef: &mut libexfat::exfat::Exfat,
+    state: &mut crate::FsckState,
+) -> exfat_utils::Result<()> {
+    for i in 0..state.vol.get_cluster_count() {
+        if ef.is_cluster_allocated(i.try_into()?)? {
+            continue;
+        }
+        let c = i + libexfat::fs::EXFAT_FIRST_DATA_CLUSTER;
+        if state.vol.is_cluster_readable(c)? {
+            continue;
+        }
+        if state.ask_to_fix(crate::report::Problem::new(
+            crate::report::ProblemType::UnreadableFreeCluster,
+            None,
+            Some(c),
+            &format!("free cluster {c:#x} is unreadable, mark bad"),
+        ))? {
+            if mark_bad(state, c)? {
+                state.fixed();
+            } else {
+                log::error!("failed to mark cluster {c:#x} bad");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+const TZO_VALID: u8 = 0x80;
+const TZO_MIN: i8 = -48; // -12:00 in 15 minute increments
+const TZO_MAX: i8 = 56; // +14:00
+const CS_MAX: u8 = 199; // 10ms increments within 2 seconds
+
+struct Timestamp {
+    name: &'static str,
+    time: usize,
+    cs: Option<usize>,
+    tzo: usize,
+}
+
+const TIMESTAMPS: [Timestamp; 3] = [
+    Timestamp {
+        name: "create",
+        time: exfat_utils::entry::FILE_CRTIME,
+        cs: Some(exfat_utils::entry::FILE_CRTIME_CS),
+        tzo: exfat_utils::entry::FILE_CRTIME_TZO,
+    },
+    Timestamp {
+        name: "modify",
+        time: exfat_utils::entry::FILE_MTIME,
+        cs: Some(exfat_utils::entry::FILE_MTIME_CS),
+        tzo: exfat_utils::entry::FILE_MTIME_TZO,
+    },
+    Timestamp {
+        name: "access",
+        time: exfat_utils::entry::FILE_ATIME,
+        cs: None,
+        tzo: exfat_utils::entry::FILE_ATIME_TZO,
+    },
+];
+
+fn get_days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 => {
+            // 2100 is the only non-leap year divisible by 4 in 1980-2107
+            let year = year + 1980;
+            if year.trailing_zeros() >= 2 && year != 2100 {
+                29
+            } else {
+                28
+            }
+        }
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// seconds are stored in 2 second units, the year as an offset from 1980
+fn clamp_time(v: u32) -> u32 {
+    let year = v >> 25;
+    let month = ((v >> 21) & 0xf).clamp(1, 12);
+    let day = ((v >> 16) & 0x1f).clamp(1, get_days_in_month(year, month));
+    let hour = ((v >> 11) & 0x1f).min(23);
+    let min = ((v >> 5) & 0x3f).min(59);
+    let twosec = (v & 0x1f).min(29);
+    (year << 25) | (month << 21) | (day << 16) | (hour << 11) | (min << 5) | twosec
+}
+
+// a signed 7 bit offset in 15 minute increments, meaningful only if
+// the valid bit is set
+fn clamp_tzo(v: u8) -> u8 {
+    if v & TZO_VALID == 0 {
+        return v;
+    }
+    let offset = i8::from_ne_bytes([v << 1]) >> 1;
+    let offset = offset.clamp(TZO_MIN, TZO_MAX);
+    TZO_VALID | (offset.to_ne_bytes()[0] & !TZO_VALID)
+}
+
+fn get_problems(slot: &exfat_utils::entry::Slot) -> Vec<(crate::report::ProblemType, String)> {
+    let mut problems = vec![];
+    for ts in &TIMESTAMPS {
+        let time = slot.get_u32(ts.time);
+        if clamp_time(time) != time {
+            problems.push((
+                crate::report::ProblemType::TimestampInvalid,
+                format!("invalid {} time {time:#010x}", ts.name),
+            ));
+        }
+        if let Some(i) = ts.cs {
+            let cs = slot.get_u8(i);
+            if cs > CS_MAX {
+                problems.push((
+                    crate::report::ProblemType::TimestampInvalid,
+                    format!("invalid {} time 10ms increment {cs}", ts.name),
+                ));
+            }
+        }
+        let tzo = slot.get_u8(ts.tzo);
+        if clamp_tzo(tzo) != tzo {
+            problems.push((
+                crate::report::ProblemType::UtcOffsetInvalid,
+                format!("invalid {} time UTC offset {tzo:#04x}", ts.name),
+            ));
+        }
+    }
+    problems
+}
+
+fn fix_time(
+    state: &mut crate::FsckState,
+    es: &mut exfat_utils::entry::EntrySet,
+) -> exfat_utils::Result<()> {
+    let slot = es.get_file_mut();
+    for ts in &TIMESTAMPS {
+        slot.set_u32(ts.time, clamp_time(slot.get_u32(ts.time)));
+        if let Some(i) = ts.cs {
+            slot.set_u8(i, slot.get_u8(i).min(CS_MAX));
+        }
+        slot.set_u8(ts.tzo, clamp_tzo(slot.get_u8(ts.tzo)));
+    }
+    es.update_checksum();
+    crate::dentry::write_entry_set(&mut state.vol, es)
+}
+
+pub(crate) fn timeck(
+    state: &mut crate::FsckState,
+    path: &str,
+    sets: &mut [exfat_utils::entry::EntrySet],
+) -> exfat_utils::Result<()> {
+    for es in sets {
+        let problems = get_problems(es.get_file());
+        let Some((typ, _)) = problems.first() else {
+            continue;
+        };
+        let s = crate::dentry::get_name_string(&es.get_name());
+        let msg: Vec<&str> = problems.iter().map(|x| x.1.as_str()).collect();
+        if state.ask_to_fix(crate::report::Problem::new(
+            *typ,
+            Some(&exfat_utils::util::get_entry_path(path, &s)),
+            crate::dentry::get_start_cluster(es),
+            &format!("'{s}' in '{path}' has {}, clamp", msg.join(", ")),
+        ))? {
+            fix_time(state, es)?;
+            state.fixed();
+        }
+    }
+    Ok(())
+}
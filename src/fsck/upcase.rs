@@ -0,0 +1,102 @@
+fn replace_upcase_table(
+    vol: &mut exfat_utils::volume::Volume,
+    slot: &mut exfat_utils::entry::Slot,
+    clusters: &[u32],
+) -> exfat_utils::Result<bool> {
+    let table = &exfat_utils::uctc::UPCASE_TABLE;
+    let cluster_size = usize::try_from(vol.get_cluster_size())?;
+    if clusters.len() * cluster_size < table.len() {
+        log::error!(
+            "upcase table has {} clusters, {} bytes don't fit",
+            clusters.len(),
+            table.len()
+        );
+        return Ok(false);
+    }
+    for (i, buf) in table.chunks(cluster_size).enumerate() {
+        let offset = vol.c2o(clusters[i]);
+        vol.pwrite_structure(buf, offset, "upcase table")?;
+    }
+    slot.set_u32(
+        exfat_utils::entry::UPCASE_CHECKSUM,
+        exfat_utils::upcase::calc_checksum(table),
+    );
+    slot.set_u64(exfat_utils::entry::UPCASE_SIZE, u64::try_from(table.len())?);
+    vol.write_slot(slot)?;
+    Ok(true)
+}
+
+pub(crate) fn upcaseck(state: &mut crate::FsckState) -> exfat_utils::Result<()> {
+    // name checks use the standard table unless the volume has a valid one
+    state.upcase = exfat_utils::upcase::decompress(&exfat_utils::uctc::UPCASE_TABLE)?;
+
+    let Some(mut slot) = state
+        .vol
+        .find_root_entry(libexfat::fs::EXFAT_ENTRY_UPCASE)?
+    else {
+        state.error(crate::report::Problem::new(
+            crate::report::ProblemType::UpcaseMissing,
+            None,
+            None,
+            "upcase table entry is missing",
+        ));
+        return Ok(());
+    };
+    let start_cluster = slot.get_u32(exfat_utils::entry::UPCASE_START_CLUSTER);
+    let size = slot.get_u64(exfat_utils::entry::UPCASE_SIZE);
+    let checksum = slot.get_u32(exfat_utils::entry::UPCASE_CHECKSUM);
+
+    // the table itself can't be trusted, but the clusters hosting it
+    // are still usable for the replacement
+    let clusters = match state
+        .vol
+        .get_cluster_chain(start_cluster, false, Some(size))
+    {
+        Ok(v) => v,
+        Err(e) => {
+            state.error(crate::report::Problem::new(
+                crate::report::ProblemType::UpcaseUnreadable,
+                None,
+                Some(start_cluster),
+                &format!("upcase table at cluster {start_cluster:#x} is unreadable: {e}"),
+            ));
+            return Ok(());
+        }
+    };
+    let mut buf = state.vol.read_clusters(&clusters)?;
+    buf.truncate(usize::try_from(size)?);
+
+    let actual = exfat_utils::upcase::calc_checksum(&buf);
+    let (typ, question) = if actual == checksum {
+        match exfat_utils::upcase::decompress(&buf)
+            .and_then(|v| exfat_utils::upcase::verify(&v).map(|()| v))
+        {
+            Ok(v) => {
+                state.upcase = v;
+                return Ok(());
+            }
+            Err(e) => (
+                crate::report::ProblemType::UpcaseInvalid,
+                format!("upcase table at cluster {start_cluster:#x} is invalid: {e}"),
+            ),
+        }
+    } else {
+        (
+            crate::report::ProblemType::UpcaseChecksum,
+            format!(
+                "upcase table checksum mismatch: expected {checksum:#010x}, got {actual:#010x}"
+            ),
+        )
+    };
+
+    if state.ask_to_fix(crate::report::Problem::new(
+        typ,
+        None,
+        Some(start_cluster),
+        &format!("{question}, replace with the standard table"),
+    ))? && replace_upcase_table(&mut state.vol, &mut slot, &clusters)?
+    {
+        state.fixed();
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+/// Minimal JSON value for machine readable output.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    #[must_use]
+    pub fn new_object() -> Self {
+        Self::Object(vec![])
+    }
+
+    /// # Panics
+    pub fn insert<T: Into<Value>>(&mut self, key: &str, value: T) {
+        let Self::Object(v) = self else {
+            panic!("not an object");
+        };
+        v.push((key.to_string(), value.into()));
+    }
+
+    #[must_use]
+    pub fn to_pretty_string(&self) -> String {
+        let mut s = String::new();
+        self.write(&mut s, 0);
+        s
+    }
+
+    fn write(&self, s: &mut String, depth: usize) {
+        let indent = "  ";
+        match self {
+            Self::Null => s.push_str("null"),
+            Self::Bool(v) => s.push_str(if *v { "true" } else { "false" }),
+            Self::Number(v) => s.push_str(&v.to_string()),
+            Self::String(v) => write_string(s, v),
+            Self::Array(v) => {
+                if v.is_empty() {
+                    s.push_str("[]");
+                    return;
+                }
+                s.push_str("[\n");
+                for (i, x) in v.iter().enumerate() {
+                    s.push_str(&indent.repeat(depth + 1));
+                    x.write(s, depth + 1);
+                    s.push_str(if i + 1 == v.len() { "\n" } else { ",\n" });
+                }
+                s.push_str(&indent.repeat(depth));
+                s.push(']');
+            }
+            Self::Object(v) => {
+                if v.is_empty() {
+                    s.push_str("{}");
+                    return;
+                }
+                s.push_str("{\n");
+                for (i, (k, x)) in v.iter().enumerate() {
+                    s.push_str(&indent.repeat(depth + 1));
+                    write_string(s, k);
+                    s.push_str(": ");
+                    x.write(s, depth + 1);
+                    s.push_str(if i + 1 == v.len() { "\n" } else { ",\n" });
+                }
+                s.push_str(&indent.repeat(depth));
+                s.push('}');
+            }
+        }
+    }
+}
+
+fn write_string(s: &mut String, v: &str) {
+    s.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(s, "\\u{:04x}", u32::from(c));
+            }
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<u8> for Value {
+    fn from(v: u8) -> Self {
+        Self::Number(v.into())
+    }
+}
+
+impl From<u16> for Value {
+    fn from(v: u16) -> Self {
+        Self::Number(v.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(v: u32) -> Self {
+        Self::Number(v.into())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(v: u64) -> Self {
+        Self::Number(v)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Self {
+        Self::Number(u64::try_from(v).unwrap_or(u64::MAX))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Self::String(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Self::String(v)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        match v {
+            Some(v) => v.into(),
+            None => Self::Null,
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Self {
+        Self::Array(v.into_iter().map(Into::into).collect())
+    }
+}
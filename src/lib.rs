@@ -1,3 +1,10 @@
+pub mod entry;
+pub mod json;
+pub mod sha256;
+pub mod uctc;
+pub mod undo;
+pub mod upcase;
 pub mod util;
+pub mod volume;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -3,7 +3,6 @@ mod fat;
 mod mkexfat;
 mod rootdir;
 mod uct;
-mod uctc;
 mod vbr;
 
 const CHAR_BIT: usize = 8;
@@ -41,10 +41,7 @@ impl FsObject {
             Box<dyn crate::mkexfat::FsObjectTrait>,
         >,
     ) -> exfat_utils::Result<libexfat::fs::ExfatEntryUpcase> {
-        let mut sum = 0u32;
-        for i in 0..crate::uctc::UPCASE_TABLE.len() {
-            sum = sum.rotate_right(1) + u32::from(crate::uctc::UPCASE_TABLE[i]);
-        }
+        let sum = exfat_utils::upcase::calc_checksum(&exfat_utils::uctc::UPCASE_TABLE);
         let mut upcase = libexfat::fs::ExfatEntryUpcase::new();
         upcase.typ = libexfat::fs::EXFAT_ENTRY_UPCASE;
         upcase.checksum = sum.to_le();
@@ -54,7 +51,8 @@ impl FsObject {
                 / self.param.cluster_size,
         )? + libexfat::fs::EXFAT_FIRST_DATA_CLUSTER)
             .to_le();
-        upcase.size = u64::try_from(std::mem::size_of_val(&crate::uctc::UPCASE_TABLE))?.to_le();
+        upcase.size =
+            u64::try_from(std::mem::size_of_val(&exfat_utils::uctc::UPCASE_TABLE))?.to_le();
         Ok(upcase)
     }
 }
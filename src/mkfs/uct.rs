@@ -19,7 +19,7 @@ impl crate::mkexfat::FsObjectTrait for FsObject {
         >,
     ) -> exfat_utils::Result<u64> {
         Ok(u64::try_from(std::mem::size_of_val(
-            &crate::uctc::UPCASE_TABLE,
+            &exfat_utils::uctc::UPCASE_TABLE,
         ))?)
     }
 
@@ -32,7 +32,7 @@ impl crate::mkexfat::FsObjectTrait for FsObject {
             Box<dyn crate::mkexfat::FsObjectTrait>,
         >,
     ) -> exfat_utils::Result<()> {
-        if let Err(e) = dev.pwrite(&crate::uctc::UPCASE_TABLE, offset) {
+        if let Err(e) = dev.pwrite(&exfat_utils::uctc::UPCASE_TABLE, offset) {
             log::error!(
                 "failed to write upcase table of {} bytes",
                 self.get_size(fmap)?
@@ -0,0 +1,186 @@
+use byteorder::ByteOrder;
+use std::io::Read;
+use std::io::Write;
+
+const UNDO_MAGIC: &[u8; 8] = b"EXFUNDO1";
+const UNDO_HEADER_SIZE: usize = 16;
+const UNDO_RECORD_HEADER_SIZE: usize = 12;
+
+/// Original contents of sectors overwritten on a device, so that the
+/// device can be restored later.
+///
+/// The file starts with a header of magic, volume serial number and
+/// sector size, followed by records of byte offset, length and data.
+/// Each sector is saved only once, before its first overwrite.
+#[derive(Debug)]
+pub struct Undo {
+    file: std::fs::File,
+    sector_size: u64,
+    saved: std::collections::HashSet<u64>,
+}
+
+impl Undo {
+    /// # Errors
+    pub fn create(path: &str, volume_serial: u32, sector_size: u64) -> crate::Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let mut buf = [0; UNDO_HEADER_SIZE];
+        buf[..8].copy_from_slice(UNDO_MAGIC);
+        byteorder::LittleEndian::write_u32(&mut buf[8..12], volume_serial);
+        byteorder::LittleEndian::write_u32(&mut buf[12..16], u32::try_from(sector_size)?);
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        Ok(Self {
+            file,
+            sector_size,
+            saved: std::collections::HashSet::new(),
+        })
+    }
+
+    /// Sectors overlapping the given range which haven't been saved yet.
+    #[must_use]
+    pub fn get_unsaved_sectors(&self, offset: u64, size: u64) -> Vec<u64> {
+        let first = offset / self.sector_size;
+        let last = libexfat::div_round_up!(offset + size, self.sector_size);
+        (first..last)
+            .map(|x| x * self.sector_size)
+            .filter(|x| !self.saved.contains(x))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn get_sector_size(&self) -> u64 {
+        self.sector_size
+    }
+
+    /// Record is synced before returning, the caller may overwrite the
+    /// sector on the device afterwards.
+    ///
+    /// # Errors
+    pub fn save(&mut self, offset: u64, buf: &[u8]) -> crate::Result<()> {
+        let mut hdr = [0; UNDO_RECORD_HEADER_SIZE];
+        byteorder::LittleEndian::write_u64(&mut hdr[..8], offset);
+        byteorder::LittleEndian::write_u32(&mut hdr[8..], u32::try_from(buf.len())?);
+        self.file.write_all(&hdr)?;
+        self.file.write_all(buf)?;
+        self.file.sync_data()?;
+        self.saved.insert(offset);
+        Ok(())
+    }
+}
+
+/// Contents of an undo file.
+#[derive(Debug)]
+pub struct UndoFile {
+    pub volume_serial: u32,
+    pub sector_size: u64,
+    pub records: Vec<(u64, Vec<u8>)>,
+}
+
+/// # Errors
+pub fn read_undo_file(path: &str) -> crate::Result<UndoFile> {
+    let mut buf = vec![];
+    std::fs::File::open(path)?.read_to_end(&mut buf)?;
+    if buf.len() < UNDO_HEADER_SIZE || &buf[..8] != UNDO_MAGIC {
+        log::error!("'{path}' is not an undo file");
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+    let volume_serial = byteorder::LittleEndian::read_u32(&buf[8..12]);
+    let sector_size = u64::from(byteorder::LittleEndian::read_u32(&buf[12..16]));
+
+    let mut records = vec![];
+    let mut i = UNDO_HEADER_SIZE;
+    while i < buf.len() {
+        // a record cut short means the write that followed never happened
+        if i + UNDO_RECORD_HEADER_SIZE > buf.len() {
+            log::warn!("ignoring truncated record at {i:#x}");
+            break;
+        }
+        let offset = byteorder::LittleEndian::read_u64(&buf[i..i + 8]);
+        let size = usize::try_from(byteorder::LittleEndian::read_u32(&buf[i + 8..i + 12]))?;
+        i += UNDO_RECORD_HEADER_SIZE;
+        if i + size > buf.len() {
+            log::warn!("ignoring truncated record at {i:#x}");
+            break;
+        }
+        records.push((offset, buf[i..i + size].to_vec()));
+        i += size;
+    }
+    Ok(UndoFile {
+        volume_serial,
+        sector_size,
+        records,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    fn get_temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("exfat-utils-{name}-{}.undo", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_undo_round_trip() {
+        let path = get_temp_path("round-trip");
+        let _ = std::fs::remove_file(&path);
+        let mut undo = super::Undo::create(&path, 0x1234_5678, 512).unwrap();
+        assert_eq!(undo.get_unsaved_sectors(512, 1024), [512, 1024]);
+        undo.save(512, &[0xaa; 512]).unwrap();
+        undo.save(1024, &[0xbb; 512]).unwrap();
+        assert!(undo.get_unsaved_sectors(512, 1024).is_empty());
+        drop(undo);
+
+        // fsck crashed while writing the third record
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&4096u64.to_le_bytes()).unwrap();
+        file.write_all(&512u32.to_le_bytes()).unwrap();
+        file.write_all(&[0xcc; 100]).unwrap();
+        drop(file);
+
+        let v = super::read_undo_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(v.volume_serial, 0x1234_5678);
+        assert_eq!(v.sector_size, 512);
+        assert_eq!(v.records, [(512, vec![0xaa; 512]), (1024, vec![0xbb; 512])]);
+    }
+
+    #[test]
+    fn test_undo_truncated_record_header() {
+        let path = get_temp_path("truncated-header");
+        let _ = std::fs::remove_file(&path);
+        let mut undo = super::Undo::create(&path, 1, 4096).unwrap();
+        undo.save(0, &[0xaa; 4096]).unwrap();
+        drop(undo);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&8192u64.to_le_bytes()[..5]).unwrap();
+        drop(file);
+
+        let v = super::read_undo_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(v.records, [(0, vec![0xaa; 4096])]);
+    }
+
+    #[test]
+    fn test_undo_bad_magic() {
+        let path = get_temp_path("bad-magic");
+        std::fs::write(&path, [0; 16]).unwrap();
+        let result = super::read_undo_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,98 @@
+fn print_version(prog: &str) {
+    exfat_utils::util::print_version(prog);
+    println!("Copyright (C) 2011-2023  Andrew Nayenko");
+    println!("Copyright (C) 2024-  Tomohiro Kusumi");
+}
+
+fn usage(prog: &str, gopt: &getopts::Options) {
+    print!(
+        "{}",
+        gopt.usage(&format!("Usage: {prog} [-f] [-V] <undo-file> <device>"))
+    );
+}
+
+fn undo(f: &str, spec: &str, force: bool) -> exfat_utils::Result<usize> {
+    let undo = exfat_utils::undo::read_undo_file(f)?;
+    let mut dev = libexfat::open(spec, "rw")?;
+    let sb = exfat_utils::volume::read_super_block(&mut dev, 0)?;
+    let volume_serial = u32::from_le(sb.volume_serial);
+    if volume_serial != undo.volume_serial {
+        log::error!(
+            "volume serial number {volume_serial:#010x} on '{spec}' mismatches {:#010x} in '{f}'",
+            undo.volume_serial
+        );
+        if !force {
+            return Err(Box::new(nix::errno::Errno::EINVAL));
+        }
+    }
+    if sb.get_sector_size() != undo.sector_size {
+        log::error!(
+            "sector size {} on '{spec}' mismatches {} in '{f}'",
+            sb.get_sector_size(),
+            undo.sector_size
+        );
+        if !force {
+            return Err(Box::new(nix::errno::Errno::EINVAL));
+        }
+    }
+    for (offset, buf) in &undo.records {
+        log::debug!("writing {} bytes at {offset:#x}", buf.len());
+        dev.pwrite(buf, *offset)?;
+    }
+    dev.fsync()?;
+    Ok(undo.records.len())
+}
+
+fn main() {
+    if let Err(e) = exfat_utils::util::init_std_logger() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    let prog = &args[0];
+
+    let mut gopt = getopts::Options::new();
+    gopt.optflag(
+        "f",
+        "",
+        "Replay the undo file even if the volume serial number or \
+        sector size of the device don't match.",
+    );
+    gopt.optflag("V", "version", "Print version and copyright.");
+    gopt.optflag("h", "help", "Print usage.");
+
+    let matches = match gopt.parse(&args[1..]) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("{e}");
+            usage(prog, &gopt);
+            std::process::exit(1);
+        }
+    };
+    if matches.opt_present("V") {
+        print_version(prog);
+        std::process::exit(0);
+    }
+    if matches.opt_present("help") {
+        usage(prog, &gopt);
+        std::process::exit(0);
+    }
+    let force = matches.opt_present("f");
+
+    let args = matches.free;
+    if args.len() != 2 {
+        usage(prog, &gopt);
+        std::process::exit(1);
+    }
+    let f = &args[0];
+    let spec = &args[1];
+
+    match undo(f, spec, force) {
+        Ok(v) => println!("Restored {v} sectors on {spec}."),
+        Err(e) => {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+pub const UPCASE_CHARS: usize = 0x10000;
+
+#[must_use]
+pub fn calc_checksum(buf: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for x in buf {
+        sum = sum.rotate_right(1).wrapping_add(u32::from(*x));
+    }
+    sum
+}
+
+/// # Errors
+pub fn decompress(buf: &[u8]) -> crate::Result<Vec<u16>> {
+    if buf.is_empty() || (buf.len() & 1) != 0 {
+        log::error!("invalid upcase table size {}", buf.len());
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+    let src: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|x| u16::from_le_bytes([x[0], x[1]]))
+        .collect();
+
+    // identity for whatever the table doesn't cover
+    let mut table: Vec<u16> = (0..=u16::MAX).collect();
+    let mut i = 0; // source index
+    let mut n = 0; // output index
+    while i < src.len() {
+        if n >= UPCASE_CHARS {
+            break;
+        }
+        // 0xffff followed by a count skips that many identity mappings
+        if src[i] == 0xffff && i + 1 < src.len() {
+            n += usize::from(src[i + 1]);
+            i += 2;
+        } else {
+            table[n] = src[i];
+            n += 1;
+            i += 1;
+        }
+    }
+    if n > UPCASE_CHARS || i < src.len() {
+        log::error!("upcase table overflows {UPCASE_CHARS} characters");
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+    Ok(table)
+}
+
+/// # Errors
+pub fn verify(table: &[u16]) -> crate::Result<()> {
+    if table.len() != UPCASE_CHARS {
+        return Err(Box::new(nix::errno::Errno::EINVAL));
+    }
+    // ASCII must map a-z to A-Z and everything else to itself
+    for c in 0..0x80u16 {
+        let expected = if (u16::from(b'a')..=u16::from(b'z')).contains(&c) {
+            c - 0x20
+        } else {
+            c
+        };
+        if table[usize::from(c)] != expected {
+            log::error!(
+                "upcase table maps {c:#06x} to {:#06x}, expected {expected:#06x}",
+                table[usize::from(c)]
+            );
+            return Err(Box::new(nix::errno::Errno::EINVAL));
+        }
+    }
+    Ok(())
+}
@@ -39,3 +39,127 @@ pub fn init_std_logger() -> Result<(), log::SetLoggerError> {
     );
     env_logger::try_init_from_env(env)
 }
+
+/// Format 16 bytes per line with the offset, hex bytes and printable
+/// ASCII characters, as hexdump -C does.
+#[must_use]
+pub fn hexdump(buf: &[u8], offset: u64) -> Vec<String> {
+    let mut v = vec![];
+    let mut offset = offset;
+    for x in buf.chunks(16) {
+        let hex: Vec<String> = x.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = x
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    char::from(*b)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        v.push(format!("{offset:08x}  {:<47}  |{ascii}|", hex.join(" ")));
+        offset += 16;
+    }
+    v
+}
+
+/// Path of a directory entry given the path of its directory.
+#[must_use]
+pub fn get_entry_path(path: &str, name: &str) -> String {
+    if path == "/" {
+        format!("/{name}")
+    } else {
+        format!("{path}/{name}")
+    }
+}
+
+/// Node passed to the `walk_tree` callback.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Visit {
+    /// A directory about to have its entries visited.
+    Directory,
+    /// An entry of the directory last visited as `Directory`.
+    Entry,
+}
+
+// Visit a directory and its entries. Subdirectories are appended to dirs
+// with their node reference held, the caller is responsible to put them.
+fn walk_directory<F>(
+    ef: &mut libexfat::exfat::Exfat,
+    dnid: libexfat::node::Nid,
+    path: &str,
+    f: &mut F,
+    dirs: &mut Vec<(libexfat::node::Nid, String)>,
+) -> crate::Result<bool>
+where
+    F: FnMut(&mut libexfat::exfat::Exfat, libexfat::node::Nid, &str, Visit) -> crate::Result<bool>,
+{
+    if !f(ef, dnid, path, Visit::Directory)? {
+        return Ok(false);
+    }
+    let mut c = ef.opendir_cursor(dnid)?;
+    loop {
+        let nid = match ef.readdir_cursor(&mut c) {
+            Ok(v) => v,
+            Err(e) => {
+                if let libexfat::Error::Errno(e) = e {
+                    if e == nix::errno::Errno::ENOENT {
+                        break;
+                    }
+                }
+                ef.closedir_cursor(c);
+                return Err(Box::new(e));
+            }
+        };
+        let node = get_node!(ef, nid);
+        let entry_path = get_entry_path(path, node.get_name());
+        let is_directory = node.is_directory();
+        let result = f(ef, nid, &entry_path, Visit::Entry);
+        if is_directory && matches!(result, Ok(true)) {
+            dirs.push((nid, entry_path));
+        } else {
+            get_node_mut!(ef, nid).put();
+        }
+        if !matches!(result, Ok(true)) {
+            ef.closedir_cursor(c);
+            return result;
+        }
+    }
+    ef.closedir_cursor(c);
+    Ok(true)
+}
+
+/// Walk the tree under the given directory in the order of ls -lR, with
+/// an explicit stack of directories instead of recursion, so that deep
+/// trees don't overflow the stack. The callback returns false to stop
+/// the walk. Node references are put by the walker, except for the one
+/// of the given directory.
+///
+/// # Errors
+pub fn walk_tree<F>(
+    ef: &mut libexfat::exfat::Exfat,
+    nid: libexfat::node::Nid,
+    path: &str,
+    mut f: F,
+) -> crate::Result<()>
+where
+    F: FnMut(&mut libexfat::exfat::Exfat, libexfat::node::Nid, &str, Visit) -> crate::Result<bool>,
+{
+    let mut dirs = vec![(nid, path.to_string())];
+    let mut result = Ok(true);
+    let mut first = true;
+    while let Some((dnid, dpath)) = dirs.pop() {
+        if matches!(result, Ok(true)) {
+            let n = dirs.len();
+            result = walk_directory(ef, dnid, &dpath, &mut f, &mut dirs);
+            // visit subdirectories in the order they were read
+            dirs[n..].reverse();
+        }
+        if !first {
+            get_node_mut!(ef, dnid).put();
+        }
+        first = false;
+    }
+    result.map(|_| ())
+}
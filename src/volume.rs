@@ -0,0 +1,442 @@
+use byteorder::ByteOrder;
+use std::io::Write;
+
+// volume flags of the boot sector, not covered by the boot checksum
+pub const VOLUME_FLAG_DIRTY: u16 = 0x0002;
+pub const VOLUME_FLAG_MEDIA_FAILURE: u16 = 0x0004;
+const VOLUME_FLAGS_OFFSET: u64 = 106;
+
+// writes which would have been done, and reads return data as if they were
+struct DryRun {
+    log: Box<dyn std::io::Write>,
+    writes: Vec<(u64, Vec<u8>)>,
+}
+
+impl DryRun {
+    fn apply(&self, buf: &mut [u8], offset: u64) -> crate::Result<()> {
+        let end = offset + u64::try_from(buf.len())?;
+        for (x, v) in &self.writes {
+            let start = std::cmp::max(offset, *x);
+            let stop = std::cmp::min(end, *x + u64::try_from(v.len())?);
+            if start < stop {
+                buf[usize::try_from(start - offset)?..usize::try_from(stop - offset)?]
+                    .copy_from_slice(&v[usize::try_from(start - x)?..usize::try_from(stop - x)?]);
+            }
+        }
+        Ok(())
+    }
+
+    // only rows which differ are dumped
+    fn write(
+        &mut self,
+        buf: &[u8],
+        offset: u64,
+        structure: &str,
+        before: &[u8],
+    ) -> crate::Result<()> {
+        writeln!(
+            self.log,
+            "write {} bytes at {offset:#x} ({structure})",
+            buf.len()
+        )?;
+        let before = crate::util::hexdump(before, offset);
+        let after = crate::util::hexdump(buf, offset);
+        let mut changed = false;
+        for (x, y) in before.iter().zip(&after) {
+            if x != y {
+                writeln!(self.log, "- {x}")?;
+                writeln!(self.log, "+ {y}")?;
+                changed = true;
+            }
+        }
+        if !changed {
+            writeln!(self.log, "  unchanged")?;
+        }
+        self.log.flush()?;
+        self.writes.push((offset, buf.to_vec()));
+        Ok(())
+    }
+}
+
+/// Raw access to on-disk structures which libexfat doesn't expose.
+pub struct Volume {
+    dev: libexfat::device::Device,
+    sb: libexfat::fs::ExfatSuperBlock,
+    undo: Option<crate::undo::Undo>,
+    dry_run: Option<DryRun>,
+}
+
+impl Volume {
+    /// # Errors
+    pub fn open(spec: &str, mode: &str) -> crate::Result<Self> {
+        let mut dev = libexfat::open(spec, mode)?;
+        let sb = read_super_block(&mut dev, 0)?;
+        if sb.oem_name != "EXFAT   ".as_bytes() {
+            log::error!("exFAT file system is not found on '{spec}'");
+            return Err(Box::new(nix::errno::Errno::EINVAL));
+        }
+        if sb.sector_bits < 9 || sb.sector_bits > 12 || sb.sector_bits + sb.spc_bits > 25 {
+            log::error!(
+                "invalid sector_bits {} spc_bits {} on '{spec}'",
+                sb.sector_bits,
+                sb.spc_bits
+            );
+            return Err(Box::new(nix::errno::Errno::EINVAL));
+        }
+        Ok(Self {
+            dev,
+            sb,
+            undo: None,
+            dry_run: None,
+        })
+    }
+
+    #[must_use]
+    pub fn get_super_block(&self) -> &libexfat::fs::ExfatSuperBlock {
+        &self.sb
+    }
+
+    /// Volume flags as read on open or last set.
+    #[must_use]
+    pub fn get_volume_state(&self) -> u16 {
+        u16::from_le(self.sb.volume_state)
+    }
+
+    /// Write volume flags of the main boot region, the backup boot region
+    /// keeps those at format time.
+    ///
+    /// # Errors
+    pub fn set_volume_state(&mut self, volume_state: u16) -> crate::Result<()> {
+        self.pwrite_structure(
+            &volume_state.to_le_bytes(),
+            VOLUME_FLAGS_OFFSET,
+            "volume flags",
+        )?;
+        self.sb.volume_state = volume_state.to_le();
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn get_sector_size(&self) -> u64 {
+        self.sb.get_sector_size()
+    }
+
+    #[must_use]
+    pub fn get_cluster_size(&self) -> u64 {
+        self.sb.get_cluster_size()
+    }
+
+    #[must_use]
+    pub fn get_cluster_count(&self) -> u32 {
+        u32::from_le(self.sb.cluster_count)
+    }
+
+    #[must_use]
+    pub fn get_rootdir_cluster(&self) -> u32 {
+        u32::from_le(self.sb.rootdir_cluster)
+    }
+
+    #[must_use]
+    pub fn c2o(&self, cluster: u32) -> u64 {
+        u64::from(u32::from_le(self.sb.cluster_sector_start)) * self.get_sector_size()
+            + u64::from(cluster - libexfat::fs::EXFAT_FIRST_DATA_CLUSTER) * self.get_cluster_size()
+    }
+
+    #[must_use]
+    pub fn cluster_invalid(&self, cluster: u32) -> bool {
+        cluster < libexfat::fs::EXFAT_FIRST_DATA_CLUSTER
+            || cluster - libexfat::fs::EXFAT_FIRST_DATA_CLUSTER >= self.get_cluster_count()
+    }
+
+    /// Log writes with a hexdump of before and after contents instead of
+    /// writing to the device.
+    pub fn set_dry_run(&mut self, log: Box<dyn std::io::Write>) {
+        self.dry_run = Some(DryRun {
+            log,
+            writes: vec![],
+        });
+    }
+
+    /// Name of the on-disk structure at the given offset.
+    #[must_use]
+    pub fn get_structure_name(&self, offset: u64) -> String {
+        let sector = offset / self.get_sector_size();
+        let fat_sector_start = u64::from(u32::from_le(self.sb.fat_sector_start));
+        let fat_sector_count = u64::from(u32::from_le(self.sb.fat_sector_count));
+        let cluster_sector_start = u64::from(u32::from_le(self.sb.cluster_sector_start));
+        if sector < 12 {
+            "main boot region".to_string()
+        } else if sector < 24 {
+            "backup boot region".to_string()
+        } else if sector >= fat_sector_start && sector < fat_sector_start + fat_sector_count {
+            "FAT".to_string()
+        } else if sector >= cluster_sector_start {
+            let c = (offset - cluster_sector_start * self.get_sector_size())
+                / self.get_cluster_size()
+                + u64::from(libexfat::fs::EXFAT_FIRST_DATA_CLUSTER);
+            format!("cluster {c:#x}")
+        } else {
+            "unused".to_string()
+        }
+    }
+
+    /// # Errors
+    pub fn pread(&mut self, size: u64, offset: u64) -> crate::Result<Vec<u8>> {
+        match self.dev.preadx(size, offset) {
+            Ok(mut v) => {
+                if let Some(x) = &self.dry_run {
+                    x.apply(&mut v, offset)?;
+                }
+                Ok(v)
+            }
+            Err(e) => {
+                log::error!("failed to read {size} bytes at {offset:#x}");
+                Err(Box::new(e))
+            }
+        }
+    }
+
+    /// Save original contents of sectors to the undo file before any
+    /// write to them.
+    pub fn set_undo(&mut self, undo: crate::undo::Undo) {
+        self.undo = Some(undo);
+    }
+
+    /// Save sectors overlapping the given range to the undo file if any,
+    /// for those about to be written without going through this volume.
+    ///
+    /// # Errors
+    pub fn save_undo(&mut self, offset: u64, size: u64) -> crate::Result<()> {
+        let Some(undo) = &self.undo else {
+            return Ok(());
+        };
+        let sector_size = undo.get_sector_size();
+        for x in undo.get_unsaved_sectors(offset, size) {
+            let buf = self.pread(sector_size, x)?;
+            if let Some(undo) = &mut self.undo {
+                undo.save(x, &buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    pub fn pwrite(&mut self, buf: &[u8], offset: u64) -> crate::Result<()> {
+        let structure = self.get_structure_name(offset);
+        self.pwrite_structure(buf, offset, &structure)
+    }
+
+    /// Same as pwrite, with the name of the structure written for logging.
+    ///
+    /// # Errors
+    pub fn pwrite_structure(
+        &mut self,
+        buf: &[u8],
+        offset: u64,
+        structure: &str,
+    ) -> crate::Result<()> {
+        if self.dry_run.is_some() {
+            let before = self.pread(u64::try_from(buf.len())?, offset)?;
+            if let Some(x) = &mut self.dry_run {
+                x.write(buf, offset, structure, &before)?;
+            }
+            return Ok(());
+        }
+        self.save_undo(offset, u64::try_from(buf.len())?)?;
+        match self.dev.pwrite(buf, offset) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                log::error!("failed to write {} bytes at {offset:#x}", buf.len());
+                Err(Box::new(e))
+            }
+        }
+    }
+
+    /// # Errors
+    pub fn fsync(&mut self) -> crate::Result<()> {
+        Ok(self.dev.fsync()?)
+    }
+
+    #[must_use]
+    pub fn get_fat_offset(&self, cluster: u32) -> u64 {
+        u64::from(u32::from_le(self.sb.fat_sector_start)) * self.get_sector_size()
+            + u64::from(cluster) * 4
+    }
+
+    /// # Errors
+    pub fn get_fat_entry(&mut self, cluster: u32) -> crate::Result<u32> {
+        let buf = self.pread(4, self.get_fat_offset(cluster))?;
+        Ok(byteorder::LittleEndian::read_u32(&buf))
+    }
+
+    /// # Errors
+    pub fn set_fat_entry(&mut self, cluster: u32, value: u32) -> crate::Result<()> {
+        let mut buf = [0; 4];
+        byteorder::LittleEndian::write_u32(&mut buf, value);
+        self.pwrite_structure(&buf, self.get_fat_offset(cluster), "FAT")
+    }
+
+    /// Read the cluster, only EIO is taken as the cluster being unreadable.
+    ///
+    /// # Errors
+    pub fn is_cluster_readable(&mut self, cluster: u32) -> crate::Result<bool> {
+        let offset = self.c2o(cluster);
+        match self.dev.preadx(self.get_cluster_size(), offset) {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let e: Box<dyn std::error::Error> = Box::new(e);
+                if is_eio(e.as_ref()) {
+                    return Ok(false);
+                }
+                log::error!("failed to read cluster {cluster:#x} at {offset:#x}");
+                Err(e)
+            }
+        }
+    }
+
+    /// # Errors
+    pub fn get_cluster_chain(
+        &mut self,
+        start_cluster: u32,
+        is_contiguous: bool,
+        size: Option<u64>,
+    ) -> crate::Result<Vec<u32>> {
+        let mut v = vec![];
+        if start_cluster == 0 {
+            return Ok(v);
+        }
+        let clusters = size.map(|x| libexfat::div_round_up!(x, self.get_cluster_size()));
+        if is_contiguous {
+            let Some(clusters) = clusters else {
+                return Err(Box::new(nix::errno::Errno::EINVAL));
+            };
+            for i in 0..clusters {
+                let c = u32::try_from(u64::from(start_cluster) + i)?;
+                if self.cluster_invalid(c) {
+                    log::error!("invalid cluster {c:#x} in contiguous chain");
+                    return Err(Box::new(nix::errno::Errno::EINVAL));
+                }
+                v.push(c);
+            }
+            return Ok(v);
+        }
+        let mut c = start_cluster;
+        loop {
+            if let Some(clusters) = clusters {
+                if u64::try_from(v.len())? >= clusters {
+                    break;
+                }
+            }
+            if self.cluster_invalid(c) {
+                log::error!("invalid cluster {c:#x} in cluster chain");
+                return Err(Box::new(nix::errno::Errno::EINVAL));
+            }
+            // a chain can't be longer than the volume, must be a loop
+            if v.len() >= usize::try_from(self.get_cluster_count())? {
+                log::error!("cluster chain starting at {start_cluster:#x} loops");
+                return Err(Box::new(nix::errno::Errno::EINVAL));
+            }
+            v.push(c);
+            c = self.get_fat_entry(c)?;
+            if c == libexfat::fs::EXFAT_CLUSTER_END {
+                break;
+            }
+        }
+        Ok(v)
+    }
+
+    /// # Errors
+    pub fn read_clusters(&mut self, clusters: &[u32]) -> crate::Result<Vec<u8>> {
+        let mut buf = vec![];
+        for c in clusters {
+            buf.extend_from_slice(&self.pread(self.get_cluster_size(), self.c2o(*c))?);
+        }
+        Ok(buf)
+    }
+
+    /// # Errors
+    pub fn read_directory(
+        &mut self,
+        start_cluster: u32,
+        is_contiguous: bool,
+        size: Option<u64>,
+    ) -> crate::Result<Vec<crate::entry::Slot>> {
+        let mut v = vec![];
+        for c in self.get_cluster_chain(start_cluster, is_contiguous, size)? {
+            let offset = self.c2o(c);
+            let buf = self.pread(self.get_cluster_size(), offset)?;
+            for (i, x) in buf.chunks_exact(libexfat::fs::EXFAT_ENTRY_SIZE).enumerate() {
+                v.push(crate::entry::Slot::new(
+                    c,
+                    offset + u64::try_from(i * libexfat::fs::EXFAT_ENTRY_SIZE)?,
+                    x.try_into()?,
+                ));
+            }
+        }
+        Ok(v)
+    }
+
+    /// Slots of the root directory up to the end of directory marker.
+    ///
+    /// # Errors
+    pub fn read_root_entries(&mut self) -> crate::Result<Vec<crate::entry::Slot>> {
+        let rootdir_cluster = self.get_rootdir_cluster();
+        let mut v = self.read_directory(rootdir_cluster, false, None)?;
+        if let Some(i) = v.iter().position(crate::entry::Slot::is_eod) {
+            v.truncate(i);
+        }
+        Ok(v)
+    }
+
+    /// First in use entry of the given type in the root directory, such as
+    /// the allocation bitmap or the upcase table.
+    ///
+    /// # Errors
+    pub fn find_root_entry(&mut self, typ: u8) -> crate::Result<Option<crate::entry::Slot>> {
+        Ok(self
+            .read_root_entries()?
+            .into_iter()
+            .find(|x| x.get_type() == typ))
+    }
+
+    /// # Errors
+    pub fn write_slot(&mut self, slot: &crate::entry::Slot) -> crate::Result<()> {
+        self.pwrite_structure(
+            slot.as_bytes(),
+            slot.get_offset(),
+            &format!(
+                "directory entry {:#04x} in cluster {:#x}",
+                slot.get_type(),
+                slot.get_cluster()
+            ),
+        )
+    }
+}
+
+/// # Errors
+/// # Panics
+pub fn read_super_block(
+    dev: &mut libexfat::device::Device,
+    offset: u64,
+) -> crate::Result<libexfat::fs::ExfatSuperBlock> {
+    let buf = dev.preadx(libexfat::fs::EXFAT_SUPER_BLOCK_SIZE_U64, offset)?;
+    let (prefix, body, suffix) = unsafe { buf.align_to::<libexfat::fs::ExfatSuperBlock>() };
+    assert!(prefix.is_empty());
+    assert!(suffix.is_empty());
+    Ok(body[0])
+}
+
+// libexfat carries the errno either as is or in an I/O error
+fn is_eio(e: &(dyn std::error::Error + 'static)) -> bool {
+    let is_io_eio = |x: &std::io::Error| x.raw_os_error() == Some(nix::errno::Errno::EIO as i32);
+    if let Some(x) = e.downcast_ref::<libexfat::Error>() {
+        match x {
+            libexfat::Error::Errno(x) => *x == nix::errno::Errno::EIO,
+            libexfat::Error::Error(x) => is_io_eio(x),
+        }
+    } else if let Some(x) = e.downcast_ref::<std::io::Error>() {
+        is_io_eio(x)
+    } else {
+        e.downcast_ref::<nix::errno::Errno>() == Some(&nix::errno::Errno::EIO)
+    }
+}